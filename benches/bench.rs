@@ -1,6 +1,5 @@
 use criterion::{BatchSize, Bencher, black_box, Criterion, criterion_group, criterion_main, ParameterizedBenchmark};
 
-use rle_decode_helper;
 use std::fmt::{Debug, Formatter, Error};
 
 #[derive(Clone)]
@@ -83,6 +82,11 @@ fn criterion_benchmark(c: &mut Criterion) {
             lookbehind: 2,
             length: 10_000,
         },
+        Inputs {
+            buffer: initial.clone(),
+            lookbehind: 1,
+            length: 10_000,
+        },
     ];
 
     c.bench("rle-impl",
@@ -90,7 +94,248 @@ fn criterion_benchmark(c: &mut Criterion) {
             .with_function("vulnerable", vulnerable)
             .with_function("lib", lib)
     );
+
+    let large_inputs = vec![
+        Inputs {
+            buffer: initial.clone(),
+            lookbehind: 333,
+            length: 1 << 20,
+        },
+        Inputs {
+            buffer: initial.clone(),
+            lookbehind: 3,
+            length: 1 << 20,
+        },
+    ];
+
+    c.bench("rle-1mib",
+        ParameterizedBenchmark::new("lib", lib, large_inputs)
+    );
+
+    // Compare with and without `--features prefetch`
+    let huge_inputs = vec![
+        Inputs {
+            buffer: initial.clone(),
+            lookbehind: 333,
+            length: 64 << 20,
+        },
+        Inputs {
+            buffer: initial.clone(),
+            lookbehind: 3,
+            length: 64 << 20,
+        },
+    ];
+
+    c.bench("rle-huge-fill",
+        ParameterizedBenchmark::new("lib", lib, huge_inputs).sample_size(10)
+    );
+}
+
+/// Many short runs, as produced by typical LZ77 streams
+const TINY_RUNS: usize = 1000;
+
+fn tiny_runs_lib(bencher: &mut Bencher, lookbehind: &usize) {
+    bencher.iter_batched(
+        || vec![7u8; 16],
+        |mut buffer| {
+            let lookbehind = black_box(*lookbehind);
+            for _ in 0..TINY_RUNS {
+                rle_decode_helper::rle_decode(&mut buffer, lookbehind, 4);
+            }
+            buffer
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn tiny_runs_unchecked(bencher: &mut Bencher, lookbehind: &usize) {
+    bencher.iter_batched(
+        || vec![7u8; 16],
+        |mut buffer| {
+            let lookbehind = black_box(*lookbehind);
+            buffer.reserve(TINY_RUNS * 4);
+            for _ in 0..TINY_RUNS {
+                unsafe { rle_decode_helper::rle_decode_unchecked(&mut buffer, lookbehind, 4) };
+            }
+            buffer
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn tiny_runs_benchmark(c: &mut Criterion) {
+    c.bench("rle-tiny-runs",
+        ParameterizedBenchmark::new("lib", tiny_runs_lib, vec![1, 3, 8])
+            .with_function("unchecked", tiny_runs_unchecked)
+    );
+}
+
+/// Lookbehind of the runs in the `rle-small-fill` group
+const SMALL_FILL_LOOKBEHIND: usize = 3;
+
+fn small_fill_naive(bencher: &mut Bencher, fill: &usize) {
+    bencher.iter_batched(
+        || vec![7u8; 16],
+        |mut buffer| {
+            let fill = black_box(*fill);
+            for _ in 0..TINY_RUNS {
+                for _ in 0..fill {
+                    let item = buffer[buffer.len() - SMALL_FILL_LOOKBEHIND];
+                    buffer.push(item);
+                }
+            }
+            buffer
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn small_fill_lib(bencher: &mut Bencher, fill: &usize) {
+    bencher.iter_batched(
+        || vec![7u8; 16],
+        |mut buffer| {
+            let fill = black_box(*fill);
+            for _ in 0..TINY_RUNS {
+                rle_decode_helper::rle_decode(&mut buffer, SMALL_FILL_LOOKBEHIND, fill);
+            }
+            buffer
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn small_fill_benchmark(c: &mut Criterion) {
+    // Where the item by item loop for short fills stops paying off
+    c.bench("rle-small-fill",
+        ParameterizedBenchmark::new("naive", small_fill_naive, vec![1, 2, 4, 8, 16, 32, 64])
+            .with_function("lib", small_fill_lib)
+    );
+}
+
+fn qoi_naive(bencher: &mut Bencher, run_length: &usize) {
+    bencher.iter_batched(
+        || vec![[10u8, 20, 30, 255]],
+        |mut out| {
+            let run_length = black_box(*run_length);
+            let pixel = *out.last().unwrap();
+            for _ in 0..run_length {
+                out.push(pixel);
+            }
+            out
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn qoi_lib(bencher: &mut Bencher, run_length: &usize) {
+    bencher.iter_batched(
+        || vec![[10u8, 20, 30, 255]],
+        |mut out| {
+            rle_decode_helper::formats::qoi_run_fill(&mut out, black_box(*run_length));
+            out
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn qoi_benchmark(c: &mut Criterion) {
+    // 62 is the longest run a single QOI chunk can encode
+    c.bench("qoi-run",
+        ParameterizedBenchmark::new("naive", qoi_naive, vec![62, 4096])
+            .with_function("lib", qoi_lib)
+    );
+}
+
+fn ring_window(bencher: &mut Bencher, window_size: &usize) {
+    let mut window = rle_decode_helper::RleRingWindow::new(*window_size);
+    window.push_literals(&vec![7u8; *window_size]).unwrap();
+    window.consume(*window_size);
+    bencher.iter(|| {
+        for _ in 0..TINY_RUNS {
+            window.copy_back(black_box(4), 4).unwrap();
+            window.consume(4);
+        }
+    })
+}
+
+fn ring_benchmark(c: &mut Criterion) {
+    // A power of two window uses masking instead of a division to wrap around
+    c.bench("ring-window",
+        ParameterizedBenchmark::new("lib", ring_window, vec![4096, 4095])
+    );
+}
+
+/// Items in the buffer before each fill of the `rle-item-size` group
+const ITEM_SIZE_LOOKBEHIND: usize = 3;
+
+fn item_size_lib<T: Copy + Default>(bencher: &mut Bencher, bytes: &usize) {
+    // The same number of bytes for every item size
+    let fill = *bytes / std::mem::size_of::<T>();
+    bencher.iter_batched(
+        || vec![T::default(); ITEM_SIZE_LOOKBEHIND],
+        |mut buffer| {
+            rle_decode_helper::rle_decode(&mut buffer, ITEM_SIZE_LOOKBEHIND, black_box(fill));
+            buffer
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn item_size_benchmark(c: &mut Criterion) {
+    // Whether wide items would gain from copies at their own alignment
+    c.bench("rle-item-size",
+        ParameterizedBenchmark::new("u32", item_size_lib::<u32>, vec![256, 64 << 10])
+            .with_function("u64", item_size_lib::<u64>)
+            .with_function("u128", item_size_lib::<u128>)
+            .with_function("[u8; 16]", item_size_lib::<[u8; 16]>)
+            .with_function("[u64; 4]", item_size_lib::<[u64; 4]>)
+    );
+}
+
+fn pattern_fill_function(bencher: &mut Bencher, fill: &usize) {
+    bencher.iter_batched(
+        || Vec::with_capacity(TINY_RUNS * *fill),
+        |mut out| {
+            let fill = black_box(*fill);
+            for _ in 0..TINY_RUNS {
+                rle_decode_helper::rle_decode_with_pattern(&[1u8, 2, 3], fill, &mut out);
+            }
+            out
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn pattern_fill_macro(bencher: &mut Bencher, fill: &usize) {
+    bencher.iter_batched(
+        || Vec::with_capacity(TINY_RUNS * *fill),
+        |mut out| {
+            let fill = black_box(*fill);
+            for _ in 0..TINY_RUNS {
+                rle_decode_helper::rle_fill!(&mut out, [1u8, 2, 3], fill);
+            }
+            out
+        },
+        BatchSize::SmallInput,
+    )
+}
+
+fn pattern_fill_benchmark(c: &mut Criterion) {
+    // Fills with a pattern known at compile time
+    c.bench("rle-fill-macro",
+        ParameterizedBenchmark::new("function", pattern_fill_function, vec![4, 16, 64, 128, 256])
+            .with_function("macro", pattern_fill_macro)
+    );
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    tiny_runs_benchmark,
+    qoi_benchmark,
+    ring_benchmark,
+    small_fill_benchmark,
+    item_size_benchmark,
+    pattern_fill_benchmark,
+);
 criterion_main!(benches);
@@ -0,0 +1,89 @@
+//! Differential fuzzing of `rle_decode` against a trivially correct implementation
+//!
+//! Run with `cargo +nightly fuzz run decode` from the crate root.
+
+#![no_main]
+
+use std::panic::{self, AssertUnwindSafe};
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use rle_decode_helper::{rle_decode, try_rle_decode, RleError};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    initial_buffer: Vec<u8>,
+    lookbehind_length: u16,
+    fill_length: u16,
+    /// Subtracts `fill_length` from `usize::MAX` instead, to hit the overflow checks
+    huge_fill: bool,
+}
+
+/// Pushes one item at a time, which is obviously correct
+fn reference(buffer: &[u8], lookbehind_length: usize, fill_length: usize) -> Vec<u8> {
+    let copy_fragment_start = buffer.len() - lookbehind_length;
+    let mut out = buffer.to_vec();
+    for i in 0..fill_length {
+        out.push(buffer[copy_fragment_start + i % lookbehind_length]);
+    }
+    out
+}
+
+/// The panic conditions documented for `rle_decode`
+fn expected_error(
+    buffer_len: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Option<RleError> {
+    if lookbehind_length == 0 {
+        Some(RleError::ZeroLookbehind)
+    } else if buffer_len == 0 {
+        Some(RleError::EmptyBuffer)
+    } else if lookbehind_length > buffer_len {
+        Some(RleError::LookbehindTooLarge { lookbehind: lookbehind_length, buffer_len })
+    } else if buffer_len.checked_add(fill_length).is_none() {
+        Some(RleError::CapacityOverflow)
+    } else {
+        None
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let lookbehind_length = usize::from(input.lookbehind_length);
+    let fill_length = if input.huge_fill {
+        usize::MAX - usize::from(input.fill_length)
+    } else {
+        usize::from(input.fill_length)
+    };
+    let buffer_len = input.initial_buffer.len();
+
+    match expected_error(buffer_len, lookbehind_length, fill_length) {
+        Some(err) => {
+            let mut buffer = input.initial_buffer.clone();
+            assert_eq!(try_rle_decode(&mut buffer, lookbehind_length, fill_length), Err(err));
+            assert_eq!(buffer, input.initial_buffer);
+
+            // libfuzzer-sys installs a hook that aborts on any panic, but this one is expected
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(|_| {}));
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                rle_decode(&mut buffer, lookbehind_length, fill_length)
+            }));
+            panic::set_hook(hook);
+            assert!(result.is_err(), "rle_decode accepted invalid input");
+        }
+        // Valid, but too large to allocate
+        None if input.huge_fill => {}
+        None => {
+            let expected = reference(&input.initial_buffer, lookbehind_length, fill_length);
+
+            let mut buffer = input.initial_buffer.clone();
+            rle_decode(&mut buffer, lookbehind_length, fill_length);
+            assert_eq!(buffer, expected);
+
+            let mut buffer = input.initial_buffer;
+            assert_eq!(try_rle_decode(&mut buffer, lookbehind_length, fill_length), Ok(()));
+            assert_eq!(buffer, expected);
+        }
+    }
+});
@@ -0,0 +1,107 @@
+use bitvec::order::BitOrder;
+use bitvec::store::BitStore;
+use bitvec::vec::BitVec;
+
+use crate::{decode_fail, fragment_start};
+
+/// Fast decoding of run length encoded bitmaps into a bit-packed vector
+///
+/// Same as [`rle_decode`](crate::rle_decode), but for single bits: repeats the last
+/// `lookbehind_length` bits of `bits` until `fill_length` bits have been appended. The
+/// fragment need not start or end on a byte or word boundary; the copies shift the bits into
+/// place in the order of the backing store `O`.
+///
+/// ```rust
+/// use bitvec::prelude::*;
+/// use rle_decode_helper::rle_decode_bits;
+///
+/// let mut bits = bitvec![1, 0, 1, 1, 0];
+/// rle_decode_bits(&mut bits, 3, 7);
+/// assert_eq!(bits, bits![1, 0, 1, 1, 0, 1, 1, 0, 1, 1, 0, 1]);
+/// ```
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+pub fn rle_decode_bits<T, O>(
+    bits: &mut BitVec<T, O>,
+    mut lookbehind_length: usize,
+    mut fill_length: usize,
+) where T: BitStore, O: BitOrder {
+    let copy_fragment_start = match fragment_start(bits.len(), lookbehind_length, fill_length) {
+        Ok(start) => start,
+        Err(err) => decode_fail(err),
+    };
+    bits.reserve(fill_length);
+
+    if lookbehind_length == 1 {
+        let bit = bits[copy_fragment_start];
+        bits.resize(bits.len() + fill_length, bit);
+        return;
+    }
+    // The same doubling as for items, the bit-level copies are done by `bitvec`
+    while fill_length > lookbehind_length {
+        append_from_within(bits, copy_fragment_start, lookbehind_length);
+        fill_length -= lookbehind_length;
+        lookbehind_length *= 2;
+    }
+    append_from_within(bits, copy_fragment_start, fill_length);
+}
+
+/// Appends a copy of the `count` bits starting at `src_start`
+///
+/// `BitVec::extend_from_within` rejects ranges that end at the last bit, which is where every
+/// fragment ends, so this copies within the grown vector instead. For `Lsb0` and `Msb0`,
+/// `copy_within` moves whole words at a time.
+fn append_from_within<T, O>(
+    bits: &mut BitVec<T, O>,
+    src_start: usize,
+    count: usize,
+) where T: BitStore, O: BitOrder {
+    if count == 0 {
+        // `copy_within` requires the destination to be in bounds, even when copying nothing
+        return;
+    }
+    let len = bits.len();
+    bits.resize(len + count, false);
+    bits.copy_within(src_start..(src_start + count), len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use bitvec::order::{Lsb0, Msb0};
+
+    use crate::rle_decode;
+
+    /// Decodes the same bits as bytes, for comparison
+    fn check<T, O>(prefix: &[bool], lookbehind: usize, fill: usize)
+    where T: BitStore, O: BitOrder {
+        let mut bits: BitVec<T, O> = prefix.iter().copied().collect();
+        rle_decode_bits(&mut bits, lookbehind, fill);
+
+        let mut expected: Vec<bool> = prefix.to_vec();
+        rle_decode(&mut expected, lookbehind, fill);
+        assert!(bits.iter().by_vals().eq(expected), "lookbehind {}, fill {}", lookbehind, fill);
+    }
+
+    #[test]
+    fn test_unaligned_fragments() {
+        let prefix: Vec<bool> = (0..77u32).map(|i| i % 3 == 0 || i % 7 == 1).collect();
+        // Fragments of odd bit counts, and fills that run across many words
+        for &lookbehind in &[1, 2, 5, 7, 8, 13, 64, 65, 77] {
+            for &fill in &[0, 1, 3, 63, 64, 65, 200, 1000] {
+                check::<usize, Lsb0>(&prefix[(77 - lookbehind)..], lookbehind, fill);
+                check::<u8, Msb0>(&prefix, lookbehind, fill);
+                check::<u16, Lsb0>(&prefix[3..], lookbehind.min(74), fill);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment larger than buffer size (6 > 5)")]
+    fn test_lookbehind_too_large() {
+        let mut bits: BitVec = BitVec::repeat(true, 5);
+        rle_decode_bits(&mut bits, 6, 1);
+    }
+}
@@ -0,0 +1,579 @@
+#[cfg(feature = "allocator_api")]
+use alloc::alloc::Allocator;
+use alloc::vec::Vec;
+use core::cmp;
+use core::ptr;
+
+#[cfg(feature = "arrayvec")]
+use arrayvec::ArrayVec;
+#[cfg(feature = "bytes")]
+use bytes::BytesMut;
+#[cfg(feature = "smallvec")]
+use smallvec::{Array, CollectionAllocErr, SmallVec};
+
+use crate::{
+    fill_single,
+    repeat_by_doubling,
+    rle_fill,
+    RleError,
+};
+#[cfg(feature = "bytes")]
+use crate::repeat_bytes;
+
+/// A growable buffer that the decode functions can append to
+///
+/// This exposes the handful of operations the decode loop needs, so it can run on containers
+/// other than `Vec`. All methods are expected to be cheap; implementations should mark them
+/// `#[inline]` so the hot loop does not pay for the abstraction.
+///
+/// # Safety
+/// Implementors must guarantee that
+/// * `len()` is the number of initialized items, and `as_slice()` returns exactly those items
+/// * after `reserve(additional)` returned, or `try_reserve(additional)` returned `Ok`,
+///   `additional` items can be appended through [`extend_from_within_unchecked`] without
+///   reallocating or writing out of bounds
+///
+/// [`extend_from_within_unchecked`]: Self::extend_from_within_unchecked
+pub unsafe trait RleBuffer<T> {
+    /// Returns the number of items in the buffer
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the buffer contains no items
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reserves capacity for at least `additional` more items
+    ///
+    /// May panic if the new capacity overflows or the allocation fails.
+    fn reserve(&mut self, additional: usize);
+
+    /// Tries to reserve capacity for at least `additional` more items, without panicking
+    ///
+    /// On success, the same guarantees as for [`reserve`] apply. The default implementation
+    /// calls [`reserve`]; buffers that can report allocation failure should override it and
+    /// return [`RleError::Alloc`] instead.
+    ///
+    /// [`reserve`]: Self::reserve
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), RleError> {
+        self.reserve(additional);
+        Ok(())
+    }
+
+    /// Returns the items currently in the buffer
+    fn as_slice(&self) -> &[T];
+
+    /// Appends a copy of the `count` items starting at `src_start` to the end of the buffer
+    ///
+    /// # Safety
+    /// * `src_start + count <= self.len()`
+    /// * capacity for at least `count` more items was set aside by an earlier call to
+    ///   `reserve` or `try_reserve`, and not used up since
+    unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize);
+
+    /// Appends `count` copies of the last item to the end of the buffer
+    ///
+    /// The default implementation doubles the run with [`extend_from_within_unchecked`].
+    /// Buffers with direct access to their storage should override it with a plain fill loop,
+    /// which is a lot faster for long runs.
+    ///
+    /// # Safety
+    /// * the buffer is not empty
+    /// * capacity for at least `count` more items was set aside by an earlier call to
+    ///   `reserve` or `try_reserve`, and not used up since
+    ///
+    /// [`extend_from_within_unchecked`]: Self::extend_from_within_unchecked
+    #[inline]
+    unsafe fn extend_from_last_unchecked(&mut self, mut count: usize) {
+        let src_start = self.len() - 1;
+        let mut run_length = 1;
+        while count > 0 {
+            let fill_size = cmp::min(run_length, count);
+            self.extend_from_within_unchecked(src_start, fill_size);
+            count -= fill_size;
+            run_length = run_length.saturating_mul(2);
+        }
+    }
+
+    /// Appends `count` items by repeating the last `lookbehind_length` items
+    ///
+    /// This is the decode loop behind all decode functions. The default implementation uses
+    /// [`extend_from_last_unchecked`] for a lookbehind of 1, and otherwise copies the fragment
+    /// with [`extend_from_within_unchecked`], doubling its size on every step. Overriding it is
+    /// only worth it for a faster strategy than that.
+    ///
+    /// # Safety
+    /// * `lookbehind_length` is not 0
+    /// * `lookbehind_length <= self.len()`
+    /// * capacity for at least `count` more items was set aside by an earlier call to
+    ///   `reserve` or `try_reserve`, and not used up since
+    ///
+    /// [`extend_from_last_unchecked`]: Self::extend_from_last_unchecked
+    /// [`extend_from_within_unchecked`]: Self::extend_from_within_unchecked
+    #[inline]
+    unsafe fn repeat_tail_unchecked(&mut self, lookbehind_length: usize, count: usize) {
+        repeat_by_doubling(self, lookbehind_length, count)
+    }
+}
+
+/// Implements [`RleBuffer`] for `Vec`, with or without the allocator parameter
+macro_rules! impl_vec_buffer {
+    ($vec:ty, [$($generics:tt)*]) => {
+        unsafe impl<$($generics)*> RleBuffer<T> for $vec where T: Copy {
+            #[inline]
+            fn len(&self) -> usize {
+                Vec::len(self)
+            }
+
+            #[inline]
+            fn reserve(&mut self, additional: usize) {
+                Vec::reserve(self, additional)
+            }
+
+            #[inline]
+            fn try_reserve(&mut self, additional: usize) -> Result<(), RleError> {
+                Vec::try_reserve(self, additional).map_err(|_| RleError::Alloc)
+            }
+
+            #[inline]
+            fn as_slice(&self) -> &[T] {
+                self
+            }
+
+            #[inline]
+            unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize) {
+                let len = Vec::len(self);
+                debug_assert!(src_start + count <= len && self.capacity() - len >= count);
+                let base = self.as_mut_ptr();
+                ptr::copy_nonoverlapping(base.add(src_start), base.add(len), count);
+                self.set_len(len + count);
+            }
+
+            #[inline]
+            unsafe fn extend_from_last_unchecked(&mut self, count: usize) {
+                let len = Vec::len(self);
+                let value = *self.get_unchecked(len - 1);
+                fill_single(self.as_mut_ptr().add(len), value, count);
+                self.set_len(len + count);
+            }
+
+            #[inline]
+            unsafe fn repeat_tail_unchecked(&mut self, lookbehind_length: usize, count: usize) {
+                let len = Vec::len(self);
+                rle_fill(self.as_mut_ptr(), len - lookbehind_length, lookbehind_length, len, count);
+                self.set_len(len + count);
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "allocator_api"))]
+impl_vec_buffer!(Vec<T>, [T]);
+#[cfg(feature = "allocator_api")]
+impl_vec_buffer!(Vec<T, A>, [T, A: Allocator]);
+
+#[cfg(feature = "smallvec")]
+unsafe impl<A> RleBuffer<A::Item> for SmallVec<A> where A: Array, A::Item: Copy {
+    #[inline]
+    fn len(&self) -> usize {
+        SmallVec::len(self)
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        SmallVec::reserve(self, additional)
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), RleError> {
+        SmallVec::try_reserve(self, additional).map_err(|err| match err {
+            CollectionAllocErr::CapacityOverflow => RleError::CapacityOverflow,
+            CollectionAllocErr::AllocErr { .. } => RleError::Alloc,
+        })
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[A::Item] {
+        self
+    }
+
+    #[inline]
+    unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize) {
+        let len = SmallVec::len(self);
+        // The base pointer has to be fetched on every call: reserve() may have moved the items
+        // from the inline storage to the heap since the last one.
+        let base = self.as_mut_ptr();
+        ptr::copy_nonoverlapping(base.add(src_start), base.add(len), count);
+        self.set_len(len + count);
+    }
+
+    #[inline]
+    unsafe fn extend_from_last_unchecked(&mut self, count: usize) {
+        let len = SmallVec::len(self);
+        let base = self.as_mut_ptr();
+        fill_single(base.add(len), *base.add(len - 1), count);
+        self.set_len(len + count);
+    }
+
+    #[inline]
+    unsafe fn repeat_tail_unchecked(&mut self, lookbehind_length: usize, count: usize) {
+        let len = SmallVec::len(self);
+        rle_fill(self.as_mut_ptr(), len - lookbehind_length, lookbehind_length, len, count);
+        self.set_len(len + count);
+    }
+}
+
+/// Fast decoding of run length encoded data into a `SmallVec`
+///
+/// Same as [`rle_decode`](crate::rle_decode), for buffers that live on the stack and only spill
+/// to the heap when they outgrow their inline capacity. The spill may happen during the decode.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+#[cfg(feature = "smallvec")]
+#[inline(always)]
+pub fn rle_decode_smallvec<A>(
+    buffer: &mut SmallVec<A>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where A: Array, A::Item: Copy {
+    crate::rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// `bytes` has no fallible reserve, so [`try_reserve`](RleBuffer::try_reserve) falls back to
+/// `reserve`: [`try_rle_decode`](crate::try_rle_decode) aborts if the allocation fails, instead
+/// of returning [`RleError::Alloc`](crate::RleError::Alloc). For lengths from untrusted input,
+/// cap the output with [`rle_decode_limited`](crate::rle_decode_limited).
+#[cfg(feature = "bytes")]
+unsafe impl RleBuffer<u8> for BytesMut {
+    #[inline]
+    fn len(&self) -> usize {
+        BytesMut::len(self)
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        // Moves the bytes to a new allocation if the current one is shared with other handles
+        // and cannot be reused, so the spare capacity afterwards is always ours to write to
+        BytesMut::reserve(self, additional)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    // Slices of a `BytesMut` only cover its initialized bytes, so all writes go through a
+    // pointer to the spare capacity, and so do all reads of fresh output.
+
+    #[inline]
+    unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize) {
+        let src = self.as_ptr().add(src_start);
+        let dst = self.spare_capacity_mut().as_mut_ptr();
+        ptr::copy_nonoverlapping(src, dst.cast(), count);
+        self.set_len(BytesMut::len(self) + count);
+    }
+
+    #[inline]
+    unsafe fn extend_from_last_unchecked(&mut self, count: usize) {
+        let len = BytesMut::len(self);
+        let value = *self.get_unchecked(len - 1);
+        fill_single(self.spare_capacity_mut().as_mut_ptr().cast(), value, count);
+        self.set_len(len + count);
+    }
+
+    #[inline]
+    unsafe fn repeat_tail_unchecked(&mut self, lookbehind_length: usize, count: usize) {
+        if lookbehind_length == 1 {
+            return self.extend_from_last_unchecked(count);
+        }
+        let len = BytesMut::len(self);
+        let src = self.as_ptr().add(len - lookbehind_length);
+        let dst = self.spare_capacity_mut().as_mut_ptr();
+        // Once the first repetition is in the spare capacity, the rest of the fill only reads
+        // from there
+        let first = cmp::min(lookbehind_length, count);
+        ptr::copy_nonoverlapping(src, dst.cast(), first);
+        if count > first {
+            repeat_bytes(dst.add(first), lookbehind_length, count - first);
+        }
+        self.set_len(len + count);
+    }
+}
+
+/// Fast decoding of run length encoded data into a `BytesMut`
+///
+/// Same as [`rle_decode`](crate::rle_decode), for network buffers of the `bytes` crate. Other
+/// handles to the same allocation, e.g. from `split_to`, are not affected: if the buffer has
+/// no spare capacity of its own, the bytes are moved to a new allocation first.
+///
+/// A failed allocation aborts, even through [`try_rle_decode`](crate::try_rle_decode), since
+/// `BytesMut` cannot reserve fallibly. For lengths from untrusted input, use
+/// [`rle_decode_limited`](crate::rle_decode_limited) to bound the output first.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+#[cfg(feature = "bytes")]
+#[inline(always)]
+pub fn rle_decode_bytes(buffer: &mut BytesMut, lookbehind_length: usize, fill_length: usize) {
+    crate::rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Only panics if the capacity is exceeded, use [`rle_decode_arrayvec`] to get an error instead
+#[cfg(feature = "arrayvec")]
+unsafe impl<T, const N: usize> RleBuffer<T> for ArrayVec<T, N> where T: Copy {
+    #[inline]
+    fn len(&self) -> usize {
+        ArrayVec::len(self)
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        if additional > self.remaining_capacity() {
+            arrayvec_capacity_fail(N);
+        }
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), RleError> {
+        if additional > self.remaining_capacity() {
+            return Err(RleError::OutputTooLarge {
+                len: ArrayVec::len(self).saturating_add(additional),
+                max_len: N,
+            });
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    #[inline]
+    unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize) {
+        let len = ArrayVec::len(self);
+        let base = self.as_mut_ptr();
+        ptr::copy_nonoverlapping(base.add(src_start), base.add(len), count);
+        self.set_len(len + count);
+    }
+
+    #[inline]
+    unsafe fn extend_from_last_unchecked(&mut self, count: usize) {
+        let len = ArrayVec::len(self);
+        let base = self.as_mut_ptr();
+        fill_single(base.add(len), *base.add(len - 1), count);
+        self.set_len(len + count);
+    }
+
+    #[inline]
+    unsafe fn repeat_tail_unchecked(&mut self, lookbehind_length: usize, count: usize) {
+        let len = ArrayVec::len(self);
+        rle_fill(self.as_mut_ptr(), len - lookbehind_length, lookbehind_length, len, count);
+        self.set_len(len + count);
+    }
+}
+
+/// Fast decoding of run length encoded data into an `ArrayVec`
+///
+/// Same as [`try_rle_decode`](crate::try_rle_decode), for fixed capacity buffers that never
+/// allocate. The output has to fit into the capacity `N`.
+///
+/// # Errors
+/// * [`RleError::OutputTooLarge`] if `buffer.len() + fill_length` exceeds `N`
+/// * everything [`try_rle_decode`](crate::try_rle_decode) reports
+#[cfg(feature = "arrayvec")]
+#[inline(always)]
+pub fn rle_decode_arrayvec<T, const N: usize>(
+    buffer: &mut ArrayVec<T, N>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<(), RleError> where T: Copy {
+    crate::rle_decode_limited(buffer, lookbehind_length, fill_length, N)
+}
+
+#[cfg(feature = "arrayvec")]
+#[inline(never)]
+#[cold]
+fn arrayvec_capacity_fail(capacity: usize) -> ! {
+    panic!("decoded output does not fit into ArrayVec of capacity {}", capacity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rle_decode;
+
+    /// A buffer that records every reservation, to check the decoder goes through the trait
+    struct Tracked {
+        items: Vec<u8>,
+        reserved: Vec<usize>,
+    }
+
+    unsafe impl RleBuffer<u8> for Tracked {
+        fn len(&self) -> usize {
+            self.items.len()
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            self.reserved.push(additional);
+            self.items.reserve(additional);
+        }
+
+        fn as_slice(&self) -> &[u8] {
+            &self.items
+        }
+
+        unsafe fn extend_from_within_unchecked(&mut self, src_start: usize, count: usize) {
+            self.items.extend_from_within_unchecked(src_start, count)
+        }
+    }
+
+    #[test]
+    fn test_custom_buffer() {
+        let mut buf = Tracked { items: vec![1, 2, 3, 4, 5], reserved: Vec::new() };
+        rle_decode(&mut buf, 3, 10);
+        assert_eq!(buf.as_slice(), &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+        assert_eq!(buf.reserved, &[10]);
+    }
+
+    #[test]
+    fn test_custom_buffer_single() {
+        for fill in 0..40 {
+            let mut buf = Tracked { items: vec![1, 2], reserved: Vec::new() };
+            rle_decode(&mut buf, 1, fill);
+            assert_eq!(buf.items.len(), 2 + fill);
+            assert!(buf.items[1..].iter().all(|&x| x == 2));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "allocator_api")]
+    fn test_custom_allocator() {
+        use alloc::alloc::{AllocError, Global, Layout};
+        use core::cell::Cell;
+        use core::ptr::NonNull;
+
+        /// Forwards to the global allocator, counting the bytes requested through it
+        struct Counting<'a>(&'a Cell<usize>);
+
+        unsafe impl Allocator for Counting<'_> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.set(self.0.get() + layout.size());
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let requested = Cell::new(0);
+        let mut buf = Vec::new_in(Counting(&requested));
+        buf.extend_from_slice(&[1u32, 2, 3]);
+        let before = requested.get();
+        rle_decode(&mut buf, 2, 1000);
+        assert!(requested.get() >= before + 1000 * 4);
+        assert_eq!(crate::try_rle_decode(&mut buf, 3, 5), Ok(()));
+
+        let mut expected = vec![1, 2, 3];
+        rle_decode(&mut expected, 2, 1000);
+        rle_decode(&mut expected, 3, 5);
+        assert_eq!(&buf[..], &expected[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn test_smallvec_inline() {
+        let mut buf: SmallVec<[u8; 16]> = SmallVec::from_slice(&[1, 2, 3, 4, 5]);
+        rle_decode_smallvec(&mut buf, 3, 10);
+        assert!(!buf.spilled());
+        assert_eq!(&buf[..], &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn test_smallvec_spill() {
+        let mut buf: SmallVec<[u16; 4]> = SmallVec::from_slice(&[1, 2, 3, 4]);
+        assert!(!buf.spilled());
+        rle_decode_smallvec(&mut buf, 2, 100);
+        assert!(buf.spilled());
+
+        let mut expected = vec![1, 2, 3, 4];
+        rle_decode(&mut expected, 2, 100);
+        assert_eq!(&buf[..], &expected[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn test_smallvec_spill_between_calls() {
+        let mut buf: SmallVec<[u8; 8]> = SmallVec::from_slice(&[7]);
+        rle_decode_smallvec(&mut buf, 1, 6);
+        assert!(!buf.spilled());
+        buf.push(9);
+        rle_decode_smallvec(&mut buf, 2, 5);
+        assert!(buf.spilled());
+        assert_eq!(&buf[..], &[7, 7, 7, 7, 7, 7, 7, 9, 7, 9, 7, 9, 7]);
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn test_bytes() {
+        let mut buf = BytesMut::from(&b"abc"[..]);
+        rle_decode_bytes(&mut buf, 2, 7);
+        assert_eq!(&buf[..], b"abcbcbcbcb");
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn test_bytes_shared() {
+        let mut buf = BytesMut::with_capacity(64);
+        buf.extend_from_slice(b"headerabcdef");
+        let mut tail = buf.split_off(9);
+        let header = buf.split_to(6);
+        // `buf` is "abc", and shares its allocation with both other handles
+        rle_decode_bytes(&mut buf, 3, 40);
+        rle_decode_bytes(&mut tail, 1, 40);
+
+        let mut expected = b"abc".to_vec();
+        rle_decode(&mut expected, 3, 40);
+        assert_eq!(&buf[..], &expected[..]);
+        assert_eq!(&header[..], b"header");
+        assert_eq!(tail.len(), 43);
+        assert!(tail.starts_with(b"def") && tail[3..].iter().all(|&b| b == b'f'));
+    }
+
+    #[test]
+    #[cfg(feature = "arrayvec")]
+    fn test_arrayvec_exactly_fits() {
+        let mut buf: ArrayVec<u8, 8> = ArrayVec::new();
+        buf.extend([1, 2, 3].iter().copied());
+        assert_eq!(rle_decode_arrayvec(&mut buf, 2, 5), Ok(()));
+        assert!(buf.is_full());
+        assert_eq!(&buf[..], &[1, 2, 3, 2, 3, 2, 3, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "arrayvec")]
+    fn test_arrayvec_one_over_capacity() {
+        let mut buf: ArrayVec<u8, 8> = ArrayVec::new();
+        buf.extend([1, 2, 3].iter().copied());
+        assert_eq!(
+            rle_decode_arrayvec(&mut buf, 1, 6),
+            Err(RleError::OutputTooLarge { len: 9, max_len: 8 }),
+        );
+        assert_eq!(&buf[..], &[1, 2, 3]);
+        assert_eq!(rle_decode_arrayvec(&mut buf, 0, 1), Err(RleError::ZeroLookbehind));
+    }
+
+    #[test]
+    #[cfg(feature = "arrayvec")]
+    #[should_panic(expected = "does not fit into ArrayVec of capacity 4")]
+    fn test_arrayvec_rle_decode_panics() {
+        let mut buf: ArrayVec<u8, 4> = ArrayVec::new();
+        buf.push(1);
+        rle_decode(&mut buf, 1, 4);
+    }
+}
@@ -0,0 +1,664 @@
+use alloc::vec::Vec;
+
+use crate::{
+    decoded_len, fragment_start, rle_decode_unchecked, rle_encode, try_rle_decode, RleBuffer,
+    RleError,
+};
+
+/// A single step of a decoded stream
+///
+/// Separates the description of what to decode from the fast implementation. Literal items
+/// are not stored in the command itself, but taken in order from a separate pool, which keeps
+/// `RleCommand` small and `Copy`.
+///
+/// A command stream is executed by [`decode_commands`], or [`rle_decode_batch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RleCommand {
+    /// Append the next `n` items of the literal pool
+    Literal(usize),
+    /// Append `fill_length` items by repeating the last `lookbehind_length` items, see
+    /// [`rle_decode`](crate::rle_decode)
+    Copy {
+        lookbehind_length: usize,
+        fill_length: usize,
+    },
+}
+
+/// Applies a whole command stream to `buffer`, taking literal items from `literals`
+///
+/// All commands are validated and the total output is reserved once before anything is
+/// decoded, which saves the per-call checks and reallocations of calling
+/// [`rle_decode`](crate::rle_decode) in a loop. The buffer is left untouched if an error is
+/// returned.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_batch, RleCommand};
+///
+/// let mut buffer = Vec::new();
+/// rle_decode_batch(&mut buffer, b"abc", &[
+///     RleCommand::Literal(2),
+///     RleCommand::Copy { lookbehind_length: 2, fill_length: 3 },
+///     RleCommand::Literal(1),
+/// ]).unwrap();
+/// assert_eq!(buffer, b"ababac");
+/// ```
+///
+/// # Errors
+/// * [`RleError::TruncatedInput`] if the commands take more items than `literals` holds
+/// * [`RleError::ZeroLookbehind`], [`RleError::EmptyBuffer`] or [`RleError::LookbehindTooLarge`]
+///   if a copy is invalid at the point in the stream where it appears
+/// * [`RleError::CapacityOverflow`] if the total output length overflows `usize`
+/// * [`RleError::Alloc`] if the memory for the output could not be reserved
+pub fn rle_decode_batch<T>(
+    buffer: &mut Vec<T>,
+    literals: &[T],
+    commands: &[RleCommand],
+) -> Result<(), RleError> where T: Copy {
+    let len = simulate(buffer.len(), literals.len(), commands)?;
+    RleBuffer::try_reserve(buffer, len - buffer.len())?;
+    let mut cursor = 0;
+    for command in commands {
+        match *command {
+            RleCommand::Literal(n) => {
+                buffer.extend_from_slice(&literals[cursor..(cursor + n)]);
+                cursor += n;
+            }
+            RleCommand::Copy { lookbehind_length, fill_length } => unsafe {
+                // Every copy was validated against the buffer length at its point in the
+                // stream, and space for the whole output was reserved above
+                rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Executes a command stream, appending to `buffer`
+///
+/// Applies the commands in order, taking literal items from `literals` as they come up. Same
+/// as [`rle_decode_batch`], so the whole stream is validated before anything is decoded.
+///
+/// ```rust
+/// use rle_decode_helper::{decode_commands, RleCommand};
+///
+/// let mut buffer = vec![b'x'];
+/// decode_commands(&mut buffer, b"yz", &[
+///     RleCommand::Copy { lookbehind_length: 1, fill_length: 2 },
+///     RleCommand::Literal(2),
+/// ]).unwrap();
+/// assert_eq!(buffer, b"xxxyz");
+/// ```
+///
+/// # Errors
+/// Everything [`rle_decode_batch`] reports. The buffer is left untouched if an error is
+/// returned.
+pub fn decode_commands<T>(
+    buffer: &mut Vec<T>,
+    literals: &[T],
+    commands: &[RleCommand],
+) -> Result<(), RleError> where T: Copy {
+    rle_decode_batch(buffer, literals, commands)
+}
+
+/// Push interface for decoding commands into a borrowed `Vec`
+///
+/// Applies commands one at a time with [`apply`], or whole streams with
+/// [`extend_from_commands`]. It also implements [`Extend`] for literal items, so it can be
+/// handed to generic code that produces them.
+///
+/// ```rust
+/// use rle_decode_helper::{RleCommand, RleSink};
+///
+/// let mut buffer = Vec::new();
+/// let mut sink = RleSink::new(&mut buffer);
+/// sink.extend(b"ab".iter().copied());
+/// sink.apply(RleCommand::Copy { lookbehind_length: 2, fill_length: 3 }, &[]).unwrap();
+/// sink.apply(RleCommand::Literal(1), b"c").unwrap();
+/// assert_eq!(buffer, b"ababac");
+/// ```
+///
+/// [`apply`]: Self::apply
+/// [`extend_from_commands`]: Self::extend_from_commands
+#[derive(Debug)]
+pub struct RleSink<'a, T> {
+    buffer: &'a mut Vec<T>,
+}
+
+impl<'a, T> RleSink<'a, T> where T: Copy {
+    /// Creates a sink that appends to `buffer`
+    pub fn new(buffer: &'a mut Vec<T>) -> Self {
+        RleSink { buffer }
+    }
+
+    /// Applies a single command
+    ///
+    /// A [`RleCommand::Literal`] of `n` items appends the first `n` items of `literals`, which
+    /// is ignored by a [`RleCommand::Copy`].
+    ///
+    /// # Errors
+    /// * [`RleError::TruncatedInput`] if `literals` holds fewer items than a literal command
+    ///   takes
+    /// * everything [`try_rle_decode`](crate::try_rle_decode) reports for a copy
+    ///
+    /// Nothing is appended if an error is returned.
+    pub fn apply(&mut self, command: RleCommand, literals: &[T]) -> Result<(), RleError> {
+        match command {
+            RleCommand::Literal(n) => {
+                let literals = literals.get(..n).ok_or(RleError::TruncatedInput)?;
+                if decoded_len(self.buffer.len(), n).is_none() {
+                    return Err(RleError::CapacityOverflow);
+                }
+                self.buffer.try_reserve(n).map_err(|_| RleError::Alloc)?;
+                self.buffer.extend_from_slice(literals);
+                Ok(())
+            }
+            RleCommand::Copy { lookbehind_length, fill_length } => {
+                try_rle_decode(self.buffer, lookbehind_length, fill_length)
+            }
+        }
+    }
+
+    /// Applies a whole command stream, taking literal items from `literals` in order
+    ///
+    /// Same as [`rle_decode_batch`], so the whole stream is validated before anything is
+    /// appended.
+    ///
+    /// # Errors
+    /// Everything [`rle_decode_batch`] reports.
+    pub fn extend_from_commands(
+        &mut self,
+        commands: &[RleCommand],
+        literals: &[T],
+    ) -> Result<(), RleError> {
+        rle_decode_batch(self.buffer, literals, commands)
+    }
+
+    /// Returns everything in the buffer, including what was there before the sink was created
+    pub fn as_slice(&self) -> &[T] {
+        self.buffer
+    }
+}
+
+impl<'a, T> Extend<T> for RleSink<'a, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.buffer.extend(iter)
+    }
+}
+
+impl<'a, 'b, T> Extend<&'b T> for RleSink<'a, T> where T: Copy + 'b {
+    fn extend<I: IntoIterator<Item = &'b T>>(&mut self, iter: I) {
+        self.buffer.extend(iter)
+    }
+}
+
+/// Checks a whole command stream without decoding it, and returns the final buffer length
+///
+/// Simulates the buffer length from `initial_len` on, and checks every command against it
+/// like [`rle_decode_batch`] does, except that the literal pool is not known here. This rejects
+/// corrupt streams before anything is allocated, and the result is the exact capacity needed.
+///
+/// ```rust
+/// use rle_decode_helper::{validate_commands, RleCommand};
+///
+/// let commands = [
+///     RleCommand::Literal(2),
+///     RleCommand::Copy { lookbehind_length: 2, fill_length: 3 },
+/// ];
+/// assert_eq!(validate_commands(0, &commands), Ok(5));
+/// assert!(validate_commands(0, &commands[1..]).is_err());
+/// ```
+///
+/// # Errors
+/// * [`RleError::ZeroLookbehind`], [`RleError::EmptyBuffer`] or [`RleError::LookbehindTooLarge`]
+///   if a copy is invalid at the point in the stream where it appears
+/// * [`RleError::CapacityOverflow`] if the total length overflows `usize`
+pub fn validate_commands(initial_len: usize, commands: &[RleCommand]) -> Result<usize, RleError> {
+    // No literal pool can hold more than `usize::MAX` items, and the length overflows first
+    simulate(initial_len, usize::MAX, commands)
+}
+
+/// Validates `commands` against the buffer length and the size of the literal pool, and
+/// returns the final length
+fn simulate(
+    initial_len: usize,
+    literals_len: usize,
+    commands: &[RleCommand],
+) -> Result<usize, RleError> {
+    let mut len = initial_len;
+    let mut literals_used = 0;
+    for command in commands {
+        match *command {
+            RleCommand::Literal(n) => {
+                literals_used = decoded_len(literals_used, n)
+                    .filter(|&used| used <= literals_len)
+                    .ok_or(RleError::TruncatedInput)?;
+                len = decoded_len(len, n).ok_or(RleError::CapacityOverflow)?;
+            }
+            RleCommand::Copy { lookbehind_length, fill_length } => {
+                fragment_start(len, lookbehind_length, fill_length)?;
+                len += fill_length;
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// Tag byte of a [`RleCommand::Literal`] in a control stream
+const TAG_LITERAL: u8 = 0;
+/// Tag byte of a [`RleCommand::Copy`] in a control stream
+const TAG_COPY: u8 = 1;
+
+/// Serializes commands into a compact byte control stream
+///
+/// Every command is a tag byte followed by its lengths as unsigned LEB128 varints:
+/// * `0x00 n` for [`RleCommand::Literal`]
+/// * `0x01 lookbehind_length fill_length` for [`RleCommand::Copy`]
+///
+/// The stream can be read back with [`parse_commands`]. Literal items are not part of it.
+pub fn write_commands(commands: &[RleCommand], out: &mut Vec<u8>) {
+    for command in commands {
+        match *command {
+            RleCommand::Literal(n) => {
+                out.push(TAG_LITERAL);
+                write_varint(n, out);
+            }
+            RleCommand::Copy { lookbehind_length, fill_length } => {
+                out.push(TAG_COPY);
+                write_varint(lookbehind_length, out);
+                write_varint(fill_length, out);
+            }
+        }
+    }
+}
+
+/// Parses a control stream in the format written by [`write_commands`]
+///
+/// ```rust
+/// use rle_decode_helper::{parse_commands, rle_decode_batch};
+///
+/// let commands = parse_commands(&[0x00, 0x02, 0x01, 0x02, 0x03]).unwrap();
+/// let mut buffer = Vec::new();
+/// rle_decode_batch(&mut buffer, b"ab", &commands).unwrap();
+/// assert_eq!(buffer, b"ababa");
+/// ```
+///
+/// # Errors
+/// * [`RleError::TruncatedInput`] if the stream ends in the middle of a command
+/// * [`RleError::MalformedInput`] for an unknown tag byte, or a length that does not fit into
+///   `usize`
+///
+/// The commands themselves are not validated, that happens when they are executed.
+pub fn parse_commands(input: &[u8]) -> Result<Vec<RleCommand>, RleError> {
+    let mut commands = Vec::new();
+    let mut pos = 0;
+    while let Some(&tag) = input.get(pos) {
+        pos += 1;
+        let command = match tag {
+            TAG_LITERAL => RleCommand::Literal(read_varint(input, &mut pos)?),
+            TAG_COPY => RleCommand::Copy {
+                lookbehind_length: read_varint(input, &mut pos)?,
+                fill_length: read_varint(input, &mut pos)?,
+            },
+            _ => return Err(RleError::MalformedInput),
+        };
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// Decodes a self-contained LZ stream of bytes, with the literals stored inline
+///
+/// The stream uses the format of [`write_commands`], except that every literal command is
+/// directly followed by its bytes:
+/// * `0x00 n` and `n` literal bytes, appended as they are
+/// * `0x01 lookbehind_length fill_length`, decoded with [`try_rle_decode`]
+///
+/// Lengths are unsigned LEB128 varints. Back-references may reach into what `out` held before
+/// the call. Such streams are written by [`rle_encode_stream`].
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_stream;
+///
+/// let mut out = Vec::new();
+/// rle_decode_stream(&[0x00, 0x02, b'a', b'b', 0x01, 0x02, 0x03, 0x00, 0x01, b'c'], &mut out)
+///     .unwrap();
+/// assert_eq!(out, b"ababac");
+/// ```
+///
+/// # Errors
+/// * [`RleError::TruncatedInput`] if the stream ends in the middle of a command
+/// * [`RleError::MalformedInput`] for an unknown tag byte, or a length that does not fit into
+///   `usize`
+/// * any error of [`try_rle_decode`] for a copy
+///
+/// `out` is left untouched if an error is returned.
+pub fn rle_decode_stream(input: &[u8], out: &mut Vec<u8>) -> Result<(), RleError> {
+    let initial_len = out.len();
+    decode_stream(input, out).inspect_err(|_| out.truncate(initial_len))
+}
+
+fn decode_stream(input: &[u8], out: &mut Vec<u8>) -> Result<(), RleError> {
+    let mut pos = 0;
+    while let Some(&tag) = input.get(pos) {
+        pos += 1;
+        match tag {
+            TAG_LITERAL => {
+                let n = read_varint(input, &mut pos)?;
+                let literals = input
+                    .get(pos..)
+                    .and_then(|rest| rest.get(..n))
+                    .ok_or(RleError::TruncatedInput)?;
+                out.try_reserve(n).map_err(|_| RleError::Alloc)?;
+                out.extend_from_slice(literals);
+                pos += n;
+            }
+            TAG_COPY => {
+                let lookbehind_length = read_varint(input, &mut pos)?;
+                let fill_length = read_varint(input, &mut pos)?;
+                try_rle_decode(out, lookbehind_length, fill_length)?;
+            }
+            _ => return Err(RleError::MalformedInput),
+        }
+    }
+    Ok(())
+}
+
+/// Encodes `input` into the format read by [`rle_decode_stream`]
+///
+/// The commands are chosen by [`rle_encode`](crate::rle_encode), with the same trade-offs.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_stream, rle_encode_stream};
+///
+/// let mut stream = Vec::new();
+/// rle_encode_stream(b"abababababc", &mut stream);
+/// let mut out = Vec::new();
+/// rle_decode_stream(&stream, &mut out).unwrap();
+/// assert_eq!(out, b"abababababc");
+/// ```
+pub fn rle_encode_stream(input: &[u8], out: &mut Vec<u8>) {
+    let mut literals = Vec::new();
+    let commands = rle_encode(input, &mut literals);
+    let mut literals = literals.as_slice();
+    for command in &commands {
+        write_commands(core::slice::from_ref(command), out);
+        if let RleCommand::Literal(n) = *command {
+            let (taken, rest) = literals.split_at(n);
+            out.extend_from_slice(taken);
+            literals = rest;
+        }
+    }
+}
+
+fn write_varint(mut value: usize, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &[u8], pos: &mut usize) -> Result<usize, RleError> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*pos).ok_or(RleError::TruncatedInput)?;
+        *pos += 1;
+        let bits = usize::from(byte & 0x7F);
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(RleError::MalformedInput);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rle_decode;
+
+    #[test]
+    fn test_batch_matches_single_calls() {
+        let commands = [
+            RleCommand::Literal(3),
+            RleCommand::Copy { lookbehind_length: 1, fill_length: 5 },
+            RleCommand::Copy { lookbehind_length: 8, fill_length: 20 },
+            RleCommand::Literal(0),
+            RleCommand::Literal(2),
+            RleCommand::Copy { lookbehind_length: 2, fill_length: 1 },
+        ];
+        let mut batch = vec![9];
+        rle_decode_batch(&mut batch, &[1, 2, 3, 4, 5], &commands).unwrap();
+
+        let mut expected = vec![9, 1, 2, 3];
+        rle_decode(&mut expected, 1, 5);
+        rle_decode(&mut expected, 8, 20);
+        expected.extend_from_slice(&[4, 5]);
+        rle_decode(&mut expected, 2, 1);
+        assert_eq!(batch, expected);
+    }
+
+    #[test]
+    fn test_decode_commands() {
+        let commands = [
+            RleCommand::Literal(3),
+            RleCommand::Copy { lookbehind_length: 2, fill_length: 5 },
+            RleCommand::Literal(1),
+        ];
+        let mut batch = Vec::new();
+        rle_decode_batch(&mut batch, &[1u8, 2, 3, 4], &commands).unwrap();
+        let mut decoded = Vec::new();
+        decode_commands(&mut decoded, &[1u8, 2, 3, 4], &commands).unwrap();
+        assert_eq!(decoded, batch);
+        assert_eq!(
+            decode_commands(&mut decoded, &[1u8], &commands),
+            Err(RleError::TruncatedInput),
+        );
+        assert_eq!(decoded, batch);
+    }
+
+    #[test]
+    fn test_batch_encode_round_trip() {
+        let input: Vec<u16> = (0..2000u16).map(|i| (i / 7) % 5 + (i / 300)).collect();
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+        let mut out = Vec::new();
+        rle_decode_batch(&mut out, &literals, &commands).unwrap();
+        assert_eq!(out, input);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let input: Vec<u8> = (0..3000u32).map(|i| ((i / 11) % 7 + i / 500) as u8).collect();
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+
+        let stored = bincode::serialize(&commands).unwrap();
+        let loaded: Vec<RleCommand> = bincode::deserialize(&stored).unwrap();
+        assert_eq!(loaded, commands);
+        let mut out = Vec::new();
+        rle_decode_batch(&mut out, &literals, &loaded).unwrap();
+        assert_eq!(out, input);
+
+        let err = RleError::LookbehindTooLarge { lookbehind: 5, buffer_len: 2 };
+        let loaded: RleError = bincode::deserialize(&bincode::serialize(&err).unwrap()).unwrap();
+        assert_eq!(loaded, err);
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let inputs: [Vec<u8>; 4] = [
+            Vec::new(),
+            b"no repeats".to_vec(),
+            vec![0; 1000],
+            (0..5000u32).map(|i| ((i / 9) % 13 + (i / 700)) as u8).collect(),
+        ];
+        for input in &inputs {
+            let mut stream = Vec::new();
+            rle_encode_stream(input, &mut stream);
+            let mut out = Vec::new();
+            rle_decode_stream(&stream, &mut out).unwrap();
+            assert_eq!(out, *input);
+        }
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let mut out = b"xy".to_vec();
+        for &(stream, err) in &[
+            (&[0x00, 0x03, b'a', b'b'][..], RleError::TruncatedInput),
+            (&[0x00, 0x01, b'a', 0x01, 0x02][..], RleError::TruncatedInput),
+            (&[0x00, 0x01, b'a', 0x02][..], RleError::MalformedInput),
+            (&[0x00, 0x01, b'a', 0x01, 0x04, 0x01][..], RleError::LookbehindTooLarge {
+                lookbehind: 4,
+                buffer_len: 3,
+            }),
+            (&[0x01, 0x00, 0x01][..], RleError::ZeroLookbehind),
+        ] {
+            assert_eq!(rle_decode_stream(stream, &mut out), Err(err));
+            assert_eq!(out, b"xy");
+        }
+        // Back-references may reach into the existing output
+        rle_decode_stream(&[0x01, 0x02, 0x03], &mut out).unwrap();
+        assert_eq!(out, b"xyxyx");
+    }
+
+    #[test]
+    fn test_batch_errors() {
+        let mut buffer = vec![1, 2];
+        let copy = |lookbehind_length, fill_length| RleCommand::Copy {
+            lookbehind_length,
+            fill_length,
+        };
+        let literal = RleCommand::Literal;
+        assert_eq!(
+            rle_decode_batch(&mut buffer, &[3, 4], &[literal(1), literal(2)]),
+            Err(RleError::TruncatedInput),
+        );
+        assert_eq!(
+            rle_decode_batch(&mut buffer, &[3], &[copy(2, 1), copy(4, 1)]),
+            Err(RleError::LookbehindTooLarge { lookbehind: 4, buffer_len: 3 }),
+        );
+        assert_eq!(
+            rle_decode_batch(&mut buffer, &[], &[copy(1, 1), copy(0, 1)]),
+            Err(RleError::ZeroLookbehind),
+        );
+        assert_eq!(
+            rle_decode_batch(&mut buffer, &[], &[copy(1, usize::MAX / 2), copy(1, usize::MAX)]),
+            Err(RleError::CapacityOverflow),
+        );
+        assert_eq!(
+            rle_decode_batch(&mut buffer, &[3], &[literal(usize::MAX)]),
+            Err(RleError::TruncatedInput),
+        );
+        assert_eq!(buffer, &[1, 2]);
+    }
+
+    #[test]
+    fn test_sink_mixed_commands() {
+        let input: Vec<u8> = b"abababcababab xx xx xx xx".to_vec();
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+
+        let mut one_by_one = Vec::new();
+        let mut sink = RleSink::new(&mut one_by_one);
+        let mut cursor = 0;
+        for &command in &commands {
+            sink.apply(command, &literals[cursor..]).unwrap();
+            if let RleCommand::Literal(n) = command {
+                cursor += n;
+            }
+        }
+        assert_eq!(sink.as_slice(), &input[..]);
+
+        let mut batched = Vec::new();
+        RleSink::new(&mut batched).extend_from_commands(&commands, &literals).unwrap();
+        assert_eq!(batched, input);
+    }
+
+    #[test]
+    fn test_sink_errors() {
+        let mut buffer = vec![1u8];
+        let mut sink = RleSink::new(&mut buffer);
+        assert_eq!(sink.apply(RleCommand::Literal(2), &[5]), Err(RleError::TruncatedInput));
+        assert_eq!(
+            sink.apply(RleCommand::Copy { lookbehind_length: 2, fill_length: 1 }, &[]),
+            Err(RleError::LookbehindTooLarge { lookbehind: 2, buffer_len: 1 }),
+        );
+        sink.extend(&[2, 3]);
+        assert_eq!(sink.apply(RleCommand::Literal(1), &[4, 5]), Ok(()));
+        assert_eq!(buffer, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let copy = |lookbehind_length, fill_length| RleCommand::Copy {
+            lookbehind_length,
+            fill_length,
+        };
+        let literal = RleCommand::Literal;
+        assert_eq!(validate_commands(7, &[]), Ok(7));
+        assert_eq!(validate_commands(2, &[copy(2, 3), literal(4), copy(9, 1)]), Ok(10));
+        assert_eq!(
+            validate_commands(2, &[copy(2, 3), copy(6, 1)]),
+            Err(RleError::LookbehindTooLarge { lookbehind: 6, buffer_len: 5 }),
+        );
+        assert_eq!(validate_commands(1, &[copy(0, 1)]), Err(RleError::ZeroLookbehind));
+        assert_eq!(
+            validate_commands(1, &[literal(usize::MAX)]),
+            Err(RleError::CapacityOverflow),
+        );
+
+        let input: Vec<u8> = (0..300u32).map(|i| (i / 9 % 4) as u8).collect();
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+        assert_eq!(validate_commands(0, &commands), Ok(input.len()));
+    }
+
+    #[test]
+    fn test_control_stream_round_trip() {
+        let commands = [
+            RleCommand::Literal(0),
+            RleCommand::Literal(127),
+            RleCommand::Copy { lookbehind_length: 128, fill_length: 300 },
+            RleCommand::Literal(usize::MAX),
+            RleCommand::Copy { lookbehind_length: usize::MAX, fill_length: 1 },
+        ];
+        let mut stream = Vec::new();
+        write_commands(&commands, &mut stream);
+        assert_eq!(&stream[..6], &[0x00, 0x00, 0x00, 0x7F, 0x01, 0x80]);
+        assert_eq!(parse_commands(&stream), Ok(commands.to_vec()));
+    }
+
+    #[test]
+    fn test_control_stream_decodes() {
+        let input: Vec<u8> = (0..500u32).map(|i| (i / 11 % 7) as u8).collect();
+        let mut literals = Vec::new();
+        let mut stream = Vec::new();
+        write_commands(&rle_encode(&input, &mut literals), &mut stream);
+
+        let mut out = Vec::new();
+        rle_decode_batch(&mut out, &literals, &parse_commands(&stream).unwrap()).unwrap();
+        assert_eq!(out, input);
+    }
+
+    #[test]
+    fn test_control_stream_errors() {
+        assert_eq!(parse_commands(&[]), Ok(Vec::new()));
+        assert_eq!(parse_commands(&[0x02, 0x00]), Err(RleError::MalformedInput));
+        assert_eq!(parse_commands(&[0x00]), Err(RleError::TruncatedInput));
+        assert_eq!(parse_commands(&[0x00, 0x80]), Err(RleError::TruncatedInput));
+        assert_eq!(parse_commands(&[0x01, 0x01]), Err(RleError::TruncatedInput));
+
+        let mut too_long = vec![0x00];
+        too_long.extend_from_slice(&[0xFF; 20]);
+        too_long.push(0x01);
+        assert_eq!(parse_commands(&too_long), Err(RleError::MalformedInput));
+    }
+}
@@ -0,0 +1,281 @@
+use alloc::vec::Vec;
+use core::cmp;
+
+use crate::{try_rle_decode, RleError};
+
+/// Streaming decoder for input that arrives in pieces
+///
+/// Keeps the already decoded items around between calls, so [`copy_back`] can reference data
+/// that was produced by earlier calls, exactly like an LZ window. Decoded items are collected
+/// with [`take_output`].
+///
+/// # Memory usage
+/// By default the decoder retains everything it ever decoded, since any of it may still be
+/// referenced. To bound memory, create it with [`with_max_window`]: back-references are then
+/// limited to the last `max_window` items, and older history is dropped once it has been taken.
+/// Memory use then stays below twice the window size plus the output not yet taken.
+/// Alternatively, drop history explicitly with [`truncate_window`] when the format says it can
+/// no longer be referenced.
+///
+/// ```rust
+/// use rle_decode_helper::RleDecoder;
+///
+/// let mut decoder = RleDecoder::new();
+/// decoder.push_literals(&[1, 2, 3]);
+/// assert_eq!(decoder.take_output(), &[1, 2, 3]);
+///
+/// decoder.copy_back(2, 5).unwrap();
+/// assert_eq!(decoder.take_output(), &[2, 3, 2, 3, 2]);
+/// ```
+///
+/// [`copy_back`]: Self::copy_back
+/// [`take_output`]: Self::take_output
+/// [`truncate_window`]: Self::truncate_window
+/// [`with_max_window`]: Self::with_max_window
+#[derive(Debug, Clone)]
+pub struct RleDecoder<T> {
+    buffer: Vec<T>,
+    output_start: usize,
+    /// Items before this index were cut off by `truncate_window`, and may only still be in the
+    /// buffer because they were not taken yet
+    history_start: usize,
+    max_window: Option<usize>,
+}
+
+impl<T> RleDecoder<T> where T: Copy {
+    /// Creates a decoder that retains all decoded items
+    pub fn new() -> Self {
+        RleDecoder {
+            buffer: Vec::new(),
+            output_start: 0,
+            history_start: 0,
+            max_window: None,
+        }
+    }
+
+    /// Creates a decoder that only allows back-references into the last `max_window` items
+    pub fn with_max_window(max_window: usize) -> Self {
+        RleDecoder {
+            buffer: Vec::new(),
+            output_start: 0,
+            history_start: 0,
+            max_window: Some(max_window),
+        }
+    }
+
+    /// Appends literal items to the output
+    pub fn push_literals(&mut self, literals: &[T]) {
+        self.buffer.extend_from_slice(literals);
+    }
+
+    /// Appends `fill_length` items by repeating the last `lookbehind_length` decoded items
+    ///
+    /// The repeated items may have been produced by any earlier call, including output that was
+    /// already taken.
+    ///
+    /// # Errors
+    /// Like [`try_rle_decode`], where the buffer is everything decoded so far, or the last
+    /// `max_window` items of it. Nothing is appended if an error is returned.
+    pub fn copy_back(
+        &mut self,
+        lookbehind_length: usize,
+        fill_length: usize,
+    ) -> Result<(), RleError> {
+        let window = self.window().len();
+        if lookbehind_length > window && self.buffer.is_empty() {
+            return Err(RleError::EmptyBuffer);
+        }
+        if lookbehind_length > window {
+            return Err(RleError::LookbehindTooLarge {
+                lookbehind: lookbehind_length,
+                buffer_len: window,
+            });
+        }
+        try_rle_decode(&mut self.buffer, lookbehind_length, fill_length)
+    }
+
+    /// Returns the items that back-references can currently reach
+    pub fn window(&self) -> &[T] {
+        let start = match self.max_window {
+            Some(max_window) => self.buffer.len().saturating_sub(max_window),
+            None => 0,
+        };
+        &self.buffer[cmp::max(start, self.history_start)..]
+    }
+
+    /// Discards all but the last `keep_last` decoded items from the window
+    ///
+    /// Later back-references may reach the kept items and everything decoded afterwards;
+    /// [`copy_back`] reports [`RleError::LookbehindTooLarge`] for any that reach further. The
+    /// memory of discarded items that were already taken is freed for reuse. Output that was
+    /// not taken yet is still returned by [`take_output`], even if it was cut off.
+    ///
+    /// This never extends the window, dropped items are gone for good.
+    ///
+    /// ```rust
+    /// use rle_decode_helper::RleDecoder;
+    ///
+    /// let mut decoder = RleDecoder::new();
+    /// decoder.push_literals(&[1, 2, 3, 4]);
+    /// decoder.take_output();
+    /// decoder.truncate_window(2);
+    /// assert_eq!(decoder.window(), &[3, 4]);
+    /// assert!(decoder.copy_back(3, 1).is_err());
+    /// ```
+    ///
+    /// [`copy_back`]: Self::copy_back
+    /// [`take_output`]: Self::take_output
+    pub fn truncate_window(&mut self, keep_last: usize) {
+        let cut_off = self.buffer.len().saturating_sub(keep_last);
+        self.history_start = cmp::max(self.history_start, cut_off);
+        let discard = cmp::min(self.history_start, self.output_start);
+        self.buffer.drain(..discard);
+        self.output_start -= discard;
+        self.history_start -= discard;
+    }
+
+    /// Returns the items decoded since the last call to [`take_output`]
+    ///
+    /// [`take_output`]: Self::take_output
+    pub fn output(&self) -> &[T] {
+        &self.buffer[self.output_start..]
+    }
+
+    /// Returns the items decoded since the last call, keeping them available for back-references
+    pub fn take_output(&mut self) -> Vec<T> {
+        let output = self.buffer[self.output_start..].to_vec();
+        self.output_start = self.buffer.len();
+        self.discard_history();
+        output
+    }
+
+    /// Drops taken items that can no longer be referenced
+    ///
+    /// Only runs once at least `max_window` items can be dropped, so the cost of moving the
+    /// window to the front of the buffer is amortized.
+    fn discard_history(&mut self) {
+        if let Some(max_window) = self.max_window {
+            let unreachable = self.buffer.len().saturating_sub(max_window);
+            let discard = cmp::min(unreachable, self.output_start);
+            if discard > 0 && discard >= max_window {
+                self.buffer.drain(..discard);
+                self.output_start -= discard;
+                self.history_start = self.history_start.saturating_sub(discard);
+            }
+        }
+    }
+}
+
+impl<T> Default for RleDecoder<T> where T: Copy {
+    fn default() -> Self {
+        RleDecoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_across_calls() {
+        let mut decoder = RleDecoder::new();
+        decoder.push_literals(&[1, 2]);
+        decoder.push_literals(&[3]);
+        decoder.copy_back(3, 4).unwrap();
+        assert_eq!(decoder.output(), &[1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(decoder.take_output(), &[1, 2, 3, 1, 2, 3, 1]);
+        assert!(decoder.output().is_empty());
+
+        decoder.copy_back(5, 2).unwrap();
+        decoder.push_literals(&[9]);
+        assert_eq!(decoder.take_output(), &[3, 1, 9]);
+        assert_eq!(decoder.window(), &[1, 2, 3, 1, 2, 3, 1, 3, 1, 9]);
+    }
+
+    #[test]
+    fn test_copy_errors() {
+        let mut decoder = RleDecoder::new();
+        assert_eq!(decoder.copy_back(1, 1), Err(RleError::EmptyBuffer));
+        decoder.push_literals(&[1u8]);
+        assert_eq!(decoder.copy_back(0, 1), Err(RleError::ZeroLookbehind));
+        assert_eq!(decoder.take_output(), &[1]);
+    }
+
+    #[test]
+    fn test_max_window() {
+        let mut decoder = RleDecoder::with_max_window(4);
+        let mut expected = Vec::new();
+        for i in 0..50u32 {
+            decoder.push_literals(&[i, i + 1, i + 2]);
+            decoder.copy_back(3, 5).unwrap();
+            expected.extend_from_slice(&decoder.take_output());
+            assert!(decoder.buffer.len() < 2 * 4 + 8);
+        }
+
+        let mut unbounded = RleDecoder::new();
+        for i in 0..50u32 {
+            unbounded.push_literals(&[i, i + 1, i + 2]);
+            unbounded.copy_back(3, 5).unwrap();
+        }
+        assert_eq!(unbounded.take_output(), expected);
+
+        assert_eq!(decoder.window().len(), 4);
+        assert_eq!(decoder.copy_back(5, 1), Err(RleError::LookbehindTooLarge {
+            lookbehind: 5,
+            buffer_len: 4,
+        }));
+    }
+
+    #[test]
+    fn test_truncate_window() {
+        let mut decoder = RleDecoder::new();
+        decoder.push_literals(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(decoder.take_output(), &[1, 2, 3, 4, 5, 6]);
+        decoder.truncate_window(3);
+        assert_eq!(decoder.buffer.len(), 3);
+        assert_eq!(decoder.window(), &[4, 5, 6]);
+
+        // Reaches exactly the kept items
+        decoder.copy_back(3, 4).unwrap();
+        assert_eq!(decoder.take_output(), &[4, 5, 6, 4]);
+
+        decoder.truncate_window(2);
+        assert_eq!(decoder.copy_back(3, 1), Err(RleError::LookbehindTooLarge {
+            lookbehind: 3,
+            buffer_len: 2,
+        }));
+        // Growing the limit again does not bring back what was dropped
+        decoder.truncate_window(100);
+        assert_eq!(decoder.window(), &[6, 4]);
+        decoder.push_literals(&[7]);
+        decoder.copy_back(3, 2).unwrap();
+        assert_eq!(decoder.take_output(), &[7, 6, 4]);
+    }
+
+    #[test]
+    fn test_truncate_window_keeps_pending_output() {
+        let mut decoder = RleDecoder::with_max_window(10);
+        decoder.push_literals(&[1, 2]);
+        decoder.take_output();
+        decoder.push_literals(&[3, 4, 5]);
+        decoder.truncate_window(1);
+        assert_eq!(decoder.window(), &[5]);
+        assert_eq!(decoder.copy_back(2, 1), Err(RleError::LookbehindTooLarge {
+            lookbehind: 2,
+            buffer_len: 1,
+        }));
+        assert_eq!(decoder.take_output(), &[3, 4, 5]);
+        decoder.copy_back(1, 2).unwrap();
+        assert_eq!(decoder.take_output(), &[5, 5]);
+    }
+
+    #[test]
+    fn test_max_window_keeps_pending_output() {
+        let mut decoder = RleDecoder::with_max_window(2);
+        decoder.push_literals(&[1, 2, 3, 4, 5, 6]);
+        decoder.copy_back(2, 2).unwrap();
+        assert_eq!(decoder.window(), &[5, 6]);
+        assert_eq!(decoder.take_output(), &[1, 2, 3, 4, 5, 6, 5, 6]);
+        assert_eq!(decoder.window(), &[5, 6]);
+    }
+}
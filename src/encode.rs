@@ -0,0 +1,136 @@
+use alloc::vec::Vec;
+
+use crate::RleCommand;
+
+/// Back-references never reach further back than this many items
+const MAX_LOOKBEHIND: usize = 1024;
+
+/// Repeats shorter than this are emitted as literals
+const MIN_FILL: usize = 2;
+
+/// Encodes `input` into a command stream for the decode functions
+///
+/// Returns the commands and appends the literal items they reference to `literals`. Replaying
+/// the commands, taking [`RleCommand::Literal`] items from `literals` in order and executing
+/// every [`RleCommand::Copy`] with [`rle_decode`](crate::rle_decode), reproduces `input` exactly.
+///
+/// This is a simple greedy encoder: at every position it picks the longest repeat of one of the
+/// last 1024 items, preferring the shortest lookbehind on ties. It is meant for interoperability
+/// and testing rather than for the best compression ratio.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_encode, RleCommand};
+///
+/// let mut literals = Vec::new();
+/// let commands = rle_encode(&[1, 2, 1, 2, 1, 2, 3], &mut literals);
+/// assert_eq!(literals, &[1, 2, 3]);
+/// assert_eq!(commands, &[
+///     RleCommand::Literal(2),
+///     RleCommand::Copy { lookbehind_length: 2, fill_length: 4 },
+///     RleCommand::Literal(1),
+/// ]);
+/// ```
+pub fn rle_encode<T>(input: &[T], literals: &mut Vec<T>) -> Vec<RleCommand> where T: PartialEq + Copy {
+    let mut commands = Vec::new();
+    let mut pending_literals = 0;
+    let mut pos = 0;
+    while pos < input.len() {
+        let (lookbehind_length, fill_length) = longest_repeat(input, pos);
+        if fill_length >= MIN_FILL {
+            if pending_literals > 0 {
+                commands.push(RleCommand::Literal(pending_literals));
+                pending_literals = 0;
+            }
+            commands.push(RleCommand::Copy { lookbehind_length, fill_length });
+            pos += fill_length;
+        } else {
+            literals.push(input[pos]);
+            pending_literals += 1;
+            pos += 1;
+        }
+    }
+    if pending_literals > 0 {
+        commands.push(RleCommand::Literal(pending_literals));
+    }
+    commands
+}
+
+/// Returns the lookbehind and length of the longest tail repeat that reproduces `input[pos..]`
+fn longest_repeat<T>(input: &[T], pos: usize) -> (usize, usize) where T: PartialEq {
+    let mut best = (0, 0);
+    for lookbehind in 1..=core::cmp::min(pos, MAX_LOOKBEHIND) {
+        // Repeating the tail means item `i` is a copy of item `i - lookbehind`, which may itself
+        // have been produced by the same repeat
+        let fill_length = input[pos..]
+            .iter()
+            .zip(&input[(pos - lookbehind)..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if fill_length > best.1 {
+            best = (lookbehind, fill_length);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rle_decode, xorshift};
+
+    fn replay<T: Copy>(commands: &[RleCommand], literals: &[T]) -> Vec<T> {
+        let mut out = Vec::new();
+        let mut cursor = 0;
+        for command in commands {
+            match *command {
+                RleCommand::Literal(n) => {
+                    out.extend_from_slice(&literals[cursor..(cursor + n)]);
+                    cursor += n;
+                }
+                RleCommand::Copy { lookbehind_length, fill_length } => {
+                    rle_decode(&mut out, lookbehind_length, fill_length);
+                }
+            }
+        }
+        assert_eq!(cursor, literals.len());
+        out
+    }
+
+    fn random_bytes(seed: &mut u32, len: usize, alphabet: u8) -> Vec<u8> {
+        (0..len).map(|_| (xorshift(seed) % u32::from(alphabet)) as u8).collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut literals: Vec<u8> = Vec::new();
+        assert!(rle_encode(&[], &mut literals).is_empty());
+        assert!(literals.is_empty());
+    }
+
+    #[test]
+    fn test_runs() {
+        let input = [7, 7, 7, 7, 7, 1, 2, 3, 1, 2, 3, 1, 2];
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+        assert_eq!(commands, &[
+            RleCommand::Literal(1),
+            RleCommand::Copy { lookbehind_length: 1, fill_length: 4 },
+            RleCommand::Literal(3),
+            RleCommand::Copy { lookbehind_length: 3, fill_length: 5 },
+        ]);
+        assert_eq!(replay(&commands, &literals), input);
+    }
+
+    #[test]
+    fn test_round_trip_random() {
+        let mut seed = 0x1234_5678;
+        for len in 0..200 {
+            for &alphabet in &[1, 2, 4, 255] {
+                let input = random_bytes(&mut seed, len, alphabet);
+                let mut literals = Vec::new();
+                let commands = rle_encode(&input, &mut literals);
+                assert_eq!(replay(&commands, &literals), input);
+            }
+        }
+    }
+}
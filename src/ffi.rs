@@ -0,0 +1,118 @@
+//! C API
+//!
+//! `extern "C"` functions for use from C and C++. They never panic or unwind across the
+//! language boundary, and report errors as negative return codes instead. To link the crate
+//! into a C project, build it with `cargo rustc --release --features capi --crate-type
+//! staticlib` (or `cdylib`).
+
+use core::{mem::MaybeUninit, slice};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{decoded_len, rle_decode_uninit};
+
+/// The call succeeded
+pub const RLE_OK: i32 = 0;
+/// A pointer argument was null
+pub const RLE_ERROR_NULL_POINTER: i32 = -1;
+/// `lookbehind_length` was 0
+pub const RLE_ERROR_ZERO_LOOKBEHIND: i32 = -2;
+/// `lookbehind_length` was larger than the number of bytes in the buffer
+pub const RLE_ERROR_LOOKBEHIND_TOO_LARGE: i32 = -3;
+/// The buffer capacity is too small for the decoded output
+pub const RLE_ERROR_CAPACITY: i32 = -4;
+/// An unexpected panic was caught. This indicates a bug in this crate.
+pub const RLE_ERROR_PANIC: i32 = -5;
+
+/// Decodes a run into a caller owned byte buffer
+///
+/// `ptr` points to a buffer of `cap` bytes, the first `len` of which are initialized. Appends
+/// `fill_length` bytes by repeating the last `lookbehind_length` bytes, like
+/// [`rle_decode`](crate::rle_decode), and stores the new length in `*out_len`. The buffer is
+/// never reallocated, so the output has to fit into `cap`.
+///
+/// Returns [`RLE_OK`] on success, or one of the negative `RLE_ERROR_*` codes. The buffer and
+/// `*out_len` are left untouched if an error is returned.
+///
+/// # Safety
+/// * `ptr` is valid for reads and writes of `cap` bytes, and the first `len` of them are
+///   initialized
+/// * `len <= cap`
+/// * `out_len` is valid for writes
+/// * nothing else accesses the buffer or `*out_len` during the call
+#[no_mangle]
+pub unsafe extern "C" fn rle_decode_u8(
+    ptr: *mut u8,
+    len: usize,
+    cap: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+    out_len: *mut usize,
+) -> i32 {
+    if ptr.is_null() || out_len.is_null() {
+        return RLE_ERROR_NULL_POINTER;
+    }
+    if lookbehind_length == 0 {
+        return RLE_ERROR_ZERO_LOOKBEHIND;
+    }
+    if lookbehind_length > len {
+        return RLE_ERROR_LOOKBEHIND_TOO_LARGE;
+    }
+    let new_len = match decoded_len(len, fill_length) {
+        Some(new_len) if new_len <= cap => new_len,
+        _ => return RLE_ERROR_CAPACITY,
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // The lookbehind is initialized and the fill region may not be, so they are viewed
+        // as separate, non-overlapping slices
+        let lookbehind = slice::from_raw_parts(ptr.add(len - lookbehind_length), lookbehind_length);
+        let fill = slice::from_raw_parts_mut(ptr.add(len) as *mut MaybeUninit<u8>, fill_length);
+        rle_decode_uninit(lookbehind, fill);
+    }));
+    match result {
+        Ok(()) => {
+            *out_len = new_len;
+            RLE_OK
+        }
+        Err(_) => RLE_ERROR_PANIC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn test_decode_through_raw_pointers() {
+        let mut buffer: Vec<u8> = Vec::with_capacity(16);
+        buffer.extend_from_slice(b"abc");
+        let mut len = 0;
+        let code = unsafe {
+            rle_decode_u8(buffer.as_mut_ptr(), buffer.len(), buffer.capacity(), 2, 5, &mut len)
+        };
+        assert_eq!(code, RLE_OK);
+        assert_eq!(len, 8);
+        unsafe { buffer.set_len(len) };
+        assert_eq!(buffer, b"abcbcbcb");
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut buffer = *b"abcd";
+        let ptr = buffer.as_mut_ptr();
+        let mut len = 42;
+        unsafe {
+            let null = ptr::null_mut();
+            assert_eq!(rle_decode_u8(null, 0, 0, 1, 1, &mut len), RLE_ERROR_NULL_POINTER);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 1, 1, ptr::null_mut()), RLE_ERROR_NULL_POINTER);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 0, 1, &mut len), RLE_ERROR_ZERO_LOOKBEHIND);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 3, 1, &mut len), RLE_ERROR_LOOKBEHIND_TOO_LARGE);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 1, 3, &mut len), RLE_ERROR_CAPACITY);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 1, usize::MAX, &mut len), RLE_ERROR_CAPACITY);
+            assert_eq!(rle_decode_u8(ptr, 2, 4, 1, 2, &mut len), RLE_OK);
+        }
+        assert_eq!(len, 4);
+        assert_eq!(&buffer, b"abbb");
+    }
+}
@@ -0,0 +1,217 @@
+use alloc::vec::Vec;
+
+use super::{overrun, truncated};
+use crate::{rle_decode, RleError};
+
+/// Decodes `BI_RLE8` compressed BMP pixel data, appending `width * height` palette indices
+/// to `out`
+///
+/// Rows are produced in the order they are stored, which is bottom-up for most BMP files.
+/// Pixels that the stream skips with an end-of-line, delta or end-of-bitmap escape are set
+/// to 0.
+///
+/// ```rust
+/// use rle_decode_helper::formats::bmp_rle8_decode;
+///
+/// // A run of three 7s, end of line, the absolute pixels 1, 2, 3 and end of bitmap
+/// let input = [0x03, 0x07, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03, 0x00, 0x00, 0x01];
+/// let mut out = Vec::new();
+/// bmp_rle8_decode(&input, 3, 3, &mut out).unwrap();
+/// assert_eq!(out, &[7, 7, 7, 1, 2, 3, 0, 0, 0]);
+/// ```
+///
+/// # Errors
+/// * [`RleError::Format`] with the offset of the control bytes if the input ends before the
+///   end-of-bitmap escape ([`FormatError::Truncated`]), or if a run, absolute block or delta
+///   goes past the end of a row or of the bitmap ([`FormatError::Overrun`])
+/// * [`RleError::CapacityOverflow`] if `width * height` overflows `usize`
+///
+/// The contents of `out` are unspecified if an error is returned.
+///
+/// [`FormatError::Truncated`]: super::FormatError::Truncated
+/// [`FormatError::Overrun`]: super::FormatError::Overrun
+pub fn bmp_rle8_decode(
+    input: &[u8],
+    width: usize,
+    height: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), RleError> {
+    bmp_rle_decode(input, width, height, out, Depth::Eight)
+}
+
+/// Decodes `BI_RLE4` compressed BMP pixel data, appending `width * height` palette indices
+/// to `out`
+///
+/// Every output byte holds one 4 bit index. Runs alternate between the high and the low nibble
+/// of their value byte, and absolute blocks are unpacked high nibble first. Otherwise this
+/// behaves like [`bmp_rle8_decode`].
+///
+/// # Errors
+/// Like [`bmp_rle8_decode`].
+pub fn bmp_rle4_decode(
+    input: &[u8],
+    width: usize,
+    height: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), RleError> {
+    bmp_rle_decode(input, width, height, out, Depth::Four)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Depth {
+    Four,
+    Eight,
+}
+
+fn bmp_rle_decode(
+    input: &[u8],
+    width: usize,
+    height: usize,
+    out: &mut Vec<u8>,
+    depth: Depth,
+) -> Result<(), RleError> {
+    let total = width.checked_mul(height).ok_or(RleError::CapacityOverflow)?;
+    let start = out.len();
+    let (mut x, mut y) = (0, 0);
+    let mut pos = 0;
+    loop {
+        let packet = pos;
+        let pair = input.get(pos..(pos + 2)).ok_or(truncated(packet))?;
+        let (count, value) = (usize::from(pair[0]), pair[1]);
+        pos += 2;
+        match (count, value) {
+            (0, 0) => {
+                // End of line
+                if y >= height {
+                    return Err(overrun(packet));
+                }
+                x = 0;
+                y += 1;
+                out.resize(start + y * width, 0);
+            }
+            (0, 1) => {
+                // End of bitmap
+                out.resize(start + total, 0);
+                return Ok(());
+            }
+            (0, 2) => {
+                let delta = input.get(pos..(pos + 2)).ok_or(truncated(packet))?;
+                pos += 2;
+                x += usize::from(delta[0]);
+                y += usize::from(delta[1]);
+                if x > width || y >= height {
+                    return Err(overrun(packet));
+                }
+                out.resize(start + y * width + x, 0);
+            }
+            (0, count) => {
+                // Absolute mode, padded to a 16 bit boundary
+                let count = usize::from(count);
+                check_row_space(x, y, count, width, height).map_err(|()| overrun(packet))?;
+                let size = match depth {
+                    Depth::Eight => count,
+                    Depth::Four => count.div_ceil(2),
+                };
+                let pixels = input.get(pos..(pos + size)).ok_or(truncated(packet))?;
+                pos += size + size % 2;
+                match depth {
+                    Depth::Eight => out.extend_from_slice(pixels),
+                    Depth::Four => {
+                        let nibbles = pixels.iter().flat_map(|&byte| [byte >> 4, byte & 0x0F]);
+                        out.extend(nibbles.take(count));
+                    }
+                }
+                x += count;
+            }
+            (count, value) => {
+                check_row_space(x, y, count, width, height).map_err(|()| overrun(packet))?;
+                match depth {
+                    Depth::Eight => {
+                        out.push(value);
+                        rle_decode(out, 1, count - 1);
+                    }
+                    Depth::Four => {
+                        out.push(value >> 4);
+                        if count > 1 {
+                            out.push(value & 0x0F);
+                            rle_decode(out, 2, count - 2);
+                        }
+                    }
+                }
+                x += count;
+            }
+        }
+    }
+}
+
+#[inline]
+fn check_row_space(
+    x: usize,
+    y: usize,
+    count: usize,
+    width: usize,
+    height: usize,
+) -> Result<(), ()> {
+    if y >= height || count > width - x {
+        return Err(());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rle8_bitmap() {
+        // The example from the BMP documentation, laid out on a 20x3 bitmap
+        let input = [
+            0x03, 0x04, 0x05, 0x06, 0x00, 0x03, 0x45, 0x56, 0x67, 0x00, 0x02, 0x78, 0x00, 0x02,
+            0x05, 0x01, 0x02, 0x78, 0x00, 0x00, 0x09, 0x1E, 0x00, 0x01,
+        ];
+        let mut out = Vec::new();
+        bmp_rle8_decode(&input, 20, 3, &mut out).unwrap();
+
+        let mut expected = vec![4, 4, 4, 6, 6, 6, 6, 6, 0x45, 0x56, 0x67, 0x78, 0x78];
+        expected.resize(20 + 18, 0);
+        expected.extend_from_slice(&[0x78, 0x78]);
+        expected.extend_from_slice(&[0x1E; 9]);
+        expected.resize(60, 0);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_rle4_bitmap() {
+        // The same example for 4 bit pixels, on a 27x3 bitmap
+        let input = [
+            0x03, 0x04, 0x05, 0x06, 0x00, 0x06, 0x45, 0x56, 0x67, 0x00, 0x04, 0x78, 0x00, 0x02,
+            0x05, 0x01, 0x04, 0x78, 0x00, 0x00, 0x09, 0x1E, 0x00, 0x01,
+        ];
+        let mut out = vec![0xAA];
+        bmp_rle4_decode(&input, 27, 3, &mut out).unwrap();
+
+        let mut expected = vec![0xAA, 0, 4, 0, 0, 6, 0, 6, 0, 4, 5, 5, 6, 6, 7, 7, 8, 7, 8];
+        expected.resize(1 + 27 + 23, 0);
+        expected.extend_from_slice(&[7, 8, 7, 8]);
+        expected.extend_from_slice(&[1, 0xE, 1, 0xE, 1, 0xE, 1, 0xE, 1]);
+        expected.resize(1 + 81, 0);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut out = Vec::new();
+        // Run past the end of the row
+        assert_eq!(bmp_rle8_decode(&[0x01, 1, 0x05, 1, 0, 1], 4, 1, &mut out), Err(overrun(2)));
+        // Absolute block past the end of the row
+        assert_eq!(bmp_rle4_decode(&[0, 3, 0x12, 0x30, 0, 1], 2, 1, &mut out), Err(overrun(0)));
+        // Delta below the last row
+        assert_eq!(bmp_rle8_decode(&[0, 2, 0, 1, 0, 1], 4, 1, &mut out), Err(overrun(0)));
+        // Pixels after the last row
+        assert_eq!(bmp_rle8_decode(&[0, 0, 1, 1, 0, 1], 4, 1, &mut out), Err(overrun(2)));
+        // Missing end of bitmap, missing absolute pixels and a cut off delta
+        assert_eq!(bmp_rle8_decode(&[0x02, 1], 4, 1, &mut out), Err(truncated(2)));
+        assert_eq!(bmp_rle8_decode(&[0x01, 1, 0, 4, 1, 2], 5, 1, &mut out), Err(truncated(2)));
+        assert_eq!(bmp_rle8_decode(&[0, 2, 1], 4, 2, &mut out), Err(truncated(0)));
+    }
+}
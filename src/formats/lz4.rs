@@ -0,0 +1,164 @@
+use alloc::vec::Vec;
+
+use super::{invalid_offset, truncated};
+use crate::{lz_copy_back, RleError};
+
+/// Decodes an LZ4 block, without the frame around it, appending the output to `out`
+///
+/// A block is a series of sequences, each a token byte followed by literals and a match. The
+/// high nibble of the token is the number of literals and the low nibble the match length minus
+/// 4; a nibble of 15 continues in the following bytes, adding each until one is not 255. After
+/// the literals comes the match distance, two bytes in little endian. The last sequence ends
+/// after its literals. Matches often overlap their own output, with a distance shorter than the
+/// length; these are copied with [`lz_copy_back`].
+///
+/// Matches may only reach back into the output of this block, not into what `out` held before.
+/// The end of block restrictions of the format, such as the last five bytes being literals, are
+/// not checked.
+///
+/// Returns the number of bytes appended.
+///
+/// ```rust
+/// use rle_decode_helper::formats::lz4_block_decode;
+///
+/// let mut out = Vec::new();
+/// // "ab", then a match of 8 bytes at distance 2, then the literal "!"
+/// assert_eq!(lz4_block_decode(&[0x24, b'a', b'b', 0x02, 0x00, 0x10, b'!'], &mut out), Ok(11));
+/// assert_eq!(out, b"ababababab!");
+/// ```
+///
+/// # Errors
+/// Returns [`RleError::Format`] with the offset of the token of the sequence that could not be
+/// decoded:
+/// * [`FormatError::Truncated`] if the input is empty or ends in the middle of a sequence
+/// * [`FormatError::InvalidOffset`] if a match distance is 0 or reaches before the start of the
+///   block output
+///
+/// All complete sequences before it have been appended to `out` in that case.
+///
+/// [`FormatError::Truncated`]: super::FormatError::Truncated
+/// [`FormatError::InvalidOffset`]: super::FormatError::InvalidOffset
+pub fn lz4_block_decode(input: &[u8], out: &mut Vec<u8>) -> Result<usize, RleError> {
+    let block_start = out.len();
+    let mut pos = 0;
+    loop {
+        let token_pos = pos;
+        let token = *input.get(pos).ok_or(truncated(token_pos))?;
+        pos += 1;
+
+        let literal_len = read_length(input, &mut pos, token >> 4).ok_or(truncated(token_pos))?;
+        let literals = pos
+            .checked_add(literal_len)
+            .and_then(|end| input.get(pos..end))
+            .ok_or(truncated(token_pos))?;
+        out.extend_from_slice(literals);
+        pos += literal_len;
+        if pos == input.len() {
+            return Ok(out.len() - block_start);
+        }
+
+        let distance = input.get(pos..(pos + 2)).ok_or(truncated(token_pos))?;
+        let distance = usize::from(u16::from_le_bytes([distance[0], distance[1]]));
+        pos += 2;
+        if distance == 0 || distance > out.len() - block_start {
+            return Err(invalid_offset(token_pos));
+        }
+        let match_len = read_length(input, &mut pos, token & 0x0F)
+            .and_then(|len| len.checked_add(4))
+            .ok_or(truncated(token_pos))?;
+        lz_copy_back(out, distance, match_len);
+    }
+}
+
+/// Reads a literal or match length that starts with `nibble` from the token
+///
+/// Returns `None` if the input ends before the length does.
+fn read_length(input: &[u8], pos: &mut usize, nibble: u8) -> Option<usize> {
+    let mut len = usize::from(nibble);
+    if nibble == 0x0F {
+        loop {
+            let byte = *input.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(usize::from(byte))?;
+            if byte != 0xFF {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_block() {
+        // Compressed by the `lz4` command line tool, with the frame removed
+        let block = [
+            0x7C, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x20, 0x68, 0x06, 0x00, 0x3F, 0x21, 0x20, 0x61,
+            0x01, 0x00, 0x3C, 0x3F, 0x20, 0x61, 0x62, 0x02, 0x00, 0x0B, 0x60, 0x20, 0x65, 0x6E,
+            0x64, 0x2E, 0x0A,
+        ];
+        let mut expected = b"Hello hello hello hello! ".to_vec();
+        expected.extend_from_slice(&[b'a'; 80]);
+        expected.push(b' ');
+        for _ in 0..16 {
+            expected.extend_from_slice(b"ab");
+        }
+        expected.extend_from_slice(b" end.\n");
+
+        let mut out = vec![0xEE];
+        assert_eq!(lz4_block_decode(&block, &mut out), Ok(144));
+        assert_eq!(out[0], 0xEE);
+        assert_eq!(out[1..], expected[..]);
+    }
+
+    #[test]
+    fn test_long_lengths() {
+        // 15 + 255 + 10 literals, then a match of 15 + 4 + 255 + 0 bytes at distance 3
+        let mut block = vec![0xFF, 0xFF, 0x0A];
+        block.extend((0..280u32).map(|i| i as u8));
+        block.extend_from_slice(&[0x03, 0x00, 0xFF, 0x00, 0x00]);
+        let mut out = Vec::new();
+        assert_eq!(lz4_block_decode(&block, &mut out), Ok(280 + 274));
+        assert!(out[..280].iter().copied().eq((0..280u32).map(|i| i as u8)));
+        for i in 280..out.len() {
+            assert_eq!(out[i], out[i - 3]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_offset() {
+        let mut out = vec![1, 2, 3];
+        // Reaches into what `out` held before the block
+        assert_eq!(lz4_block_decode(&[0x10, 7, 0x02, 0x00], &mut out), Err(invalid_offset(0)));
+        assert_eq!(out, &[1, 2, 3, 7]);
+        assert_eq!(
+            lz4_block_decode(&[0x10, 7, 0x01, 0x00, 0x00, 0x00, 0x00], &mut out),
+            Err(invalid_offset(4)),
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let cases: [&[u8]; 5] = [
+            &[],
+            &[0x30, 1, 2],
+            &[0xF0, 0xFF],
+            &[0x10, 1, 0x01],
+            &[0x1F, 1, 0x01, 0x00, 0xFF],
+        ];
+        for block in &cases {
+            let mut out = Vec::new();
+            assert_eq!(lz4_block_decode(block, &mut out), Err(truncated(0)));
+            assert!(out.len() <= 1);
+        }
+
+        // The complete first sequence is kept
+        let mut out = Vec::new();
+        let block = [0x11, 5, 0x01, 0x00, 0x20, 6];
+        assert_eq!(lz4_block_decode(&block, &mut out), Err(truncated(4)));
+        assert_eq!(out, &[5, 5, 5, 5, 5, 5]);
+    }
+}
@@ -0,0 +1,61 @@
+//! Decoders for common run length encoded file formats
+//!
+//! These parse the format specific control bytes and use the repeat functions of this crate
+//! for the runs. Corrupt input is reported as [`RleError::Format`], with the offset of the
+//! packet that could not be decoded.
+
+use core::fmt;
+
+use crate::RleError;
+
+mod bmp;
+mod lz4;
+mod packbits;
+mod qoi;
+mod tga;
+
+pub use self::bmp::{bmp_rle4_decode, bmp_rle8_decode};
+pub use self::lz4::lz4_block_decode;
+pub use self::packbits::packbits_decode;
+pub use self::qoi::qoi_run_fill;
+pub use self::tga::tga_rle_decode;
+
+/// Why a format decoder rejected its input, see [`RleError::Format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FormatError {
+    /// The input ends in the middle of a packet, or before the image is complete
+    Truncated,
+    /// A packet reaches past the end of its row, or of the image
+    Overrun,
+    /// A back-reference reaches before the start of the output
+    InvalidOffset,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FormatError::Truncated => f.write_str("input ended unexpectedly"),
+            FormatError::Overrun => f.write_str("packet reaches past the end of the image"),
+            FormatError::InvalidOffset => {
+                f.write_str("back-reference reaches before the start of the output")
+            }
+        }
+    }
+}
+
+/// The error for a packet starting at `offset` that the input ends in the middle of
+const fn truncated(offset: usize) -> RleError {
+    RleError::Format { offset, error: FormatError::Truncated }
+}
+
+/// The error for a packet starting at `offset` that does not fit into the image
+const fn overrun(offset: usize) -> RleError {
+    RleError::Format { offset, error: FormatError::Overrun }
+}
+
+/// The error for a packet starting at `offset` that references data before the output
+const fn invalid_offset(offset: usize) -> RleError {
+    RleError::Format { offset, error: FormatError::InvalidOffset }
+}
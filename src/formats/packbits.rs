@@ -0,0 +1,95 @@
+use alloc::vec::Vec;
+
+use super::truncated;
+use crate::{rle_decode, RleError};
+
+/// Decodes PackBits data, as used by TIFF and MacPaint, appending the output to `out`
+///
+/// Every run starts with a signed header byte `n`:
+/// * `0..=127`: the next `n + 1` bytes are literals
+/// * `-127..=-1`: the next byte is repeated `1 - n` times
+/// * `-128`: no-op
+///
+/// ```rust
+/// use rle_decode_helper::formats::packbits_decode;
+///
+/// let mut out = Vec::new();
+/// packbits_decode(&[0xFE, 0xAA, 0x01, 0x80, 0x00], &mut out).unwrap();
+/// assert_eq!(out, &[0xAA, 0xAA, 0xAA, 0x80, 0x00]);
+/// ```
+///
+/// # Errors
+/// Returns [`RleError::Format`] with [`FormatError::Truncated`] and the offset of the header
+/// byte if the input ends in the middle of a run. All complete runs before it have been
+/// appended to `out` in that case.
+///
+/// [`FormatError::Truncated`]: super::FormatError::Truncated
+pub fn packbits_decode(input: &[u8], out: &mut Vec<u8>) -> Result<(), RleError> {
+    let mut pos = 0;
+    while let Some(&header) = input.get(pos) {
+        let start = pos;
+        pos += 1;
+        match header as i8 {
+            -128 => {}
+            n @ 0..=127 => {
+                let count = n as usize + 1;
+                let literals = input.get(pos..(pos + count)).ok_or(truncated(start))?;
+                out.extend_from_slice(literals);
+                pos += count;
+            }
+            n => {
+                let count = (1 - isize::from(n)) as usize;
+                let value = *input.get(pos).ok_or(truncated(start))?;
+                pos += 1;
+                out.push(value);
+                rle_decode(out, 1, count - 1);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apple_sample() {
+        // Example from Apple's technical note TN1023
+        let packed = [
+            0xFE, 0xAA, 0x02, 0x80, 0x00, 0x2A, 0xFD, 0xAA, 0x03, 0x80, 0x00, 0x2A, 0x22, 0xF7,
+            0xAA,
+        ];
+        let unpacked = [
+            0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0x22,
+            0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+        ];
+        let mut out = Vec::new();
+        packbits_decode(&packed, &mut out).unwrap();
+        assert_eq!(out, &unpacked[..]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut packed = vec![0x7F];
+        packed.extend(0..128);
+        packed.extend_from_slice(&[0x81, 0x55, 0x80, 0x80]);
+        let mut out = vec![1];
+        packbits_decode(&packed, &mut out).unwrap();
+        assert_eq!(out.len(), 1 + 128 + 128);
+        assert!(out[1..129].iter().copied().eq(0..128));
+        assert!(out[129..].iter().all(|&x| x == 0x55));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut out = Vec::new();
+        let packed = [0x00, 0x11, 0x02, 0x01, 0x02];
+        assert_eq!(packbits_decode(&packed, &mut out), Err(truncated(2)));
+        assert_eq!(out, &[0x11]);
+
+        out.clear();
+        assert_eq!(packbits_decode(&[0x80, 0xFF], &mut out), Err(truncated(1)));
+        assert!(out.is_empty());
+    }
+}
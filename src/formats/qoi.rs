@@ -0,0 +1,47 @@
+use alloc::vec::Vec;
+
+use crate::rle_decode;
+
+/// Repeats the last pixel in `out` `run_length` times, as for a QOI `QOI_OP_RUN` chunk
+///
+/// The chunk stores the run length with a bias of -1, which has to be undone before calling
+/// this.
+///
+/// ```rust
+/// use rle_decode_helper::formats::qoi_run_fill;
+///
+/// let mut out = vec![[0, 0, 0, 255], [10, 20, 30, 255]];
+/// qoi_run_fill(&mut out, 3);
+/// assert_eq!(out, &[[0, 0, 0, 255], [10, 20, 30, 255], [10, 20, 30, 255], [10, 20, 30, 255],
+///                   [10, 20, 30, 255]]);
+/// ```
+///
+/// # Panics
+/// * if `out` is empty. A QOI decoder starts from the implicit pixel `[0, 0, 0, 255]`, so
+///   push that before decoding the first chunk.
+/// * if the new length overflows `usize` or the allocation fails
+#[inline]
+pub fn qoi_run_fill(out: &mut Vec<[u8; 4]>, run_length: usize) {
+    rle_decode(out, 1, run_length);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_fill() {
+        let mut out = vec![[1, 2, 3, 4]];
+        qoi_run_fill(&mut out, 0);
+        assert_eq!(out, &[[1, 2, 3, 4]]);
+        qoi_run_fill(&mut out, 62);
+        assert_eq!(out.len(), 63);
+        assert!(out.iter().all(|&pixel| pixel == [1, 2, 3, 4]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_run_fill_empty() {
+        qoi_run_fill(&mut Vec::new(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::vec::Vec;
+
+use super::{overrun, truncated};
+use crate::{try_rle_decode, RleError};
+
+/// Decodes the run length encoded pixel data of a TGA image, appending it to `out`
+///
+/// Reads packets until `pixel_count` pixels of `bytes_per_pixel` bytes each were decoded. Every
+/// packet starts with a header byte: the low 7 bits are the pixel count minus one, and the high
+/// bit selects between a run packet (one pixel, repeated) and a raw packet (that many pixels
+/// follow). Packets may cross scanline boundaries.
+///
+/// Returns the number of bytes consumed from `input`, so decoding can continue after the pixel
+/// data.
+///
+/// ```rust
+/// use rle_decode_helper::formats::tga_rle_decode;
+///
+/// let mut out = Vec::new();
+/// let consumed = tga_rle_decode(&[0x82, 7, 0x01, 1, 2, 0xFF], 1, 5, &mut out).unwrap();
+/// assert_eq!(consumed, 5);
+/// assert_eq!(out, &[7, 7, 7, 1, 2]);
+/// ```
+///
+/// # Errors
+/// * [`RleError::ZeroLookbehind`] if `bytes_per_pixel` is 0
+/// * [`RleError::CapacityOverflow`] if the size of a packet, in bytes, overflows `usize`
+/// * [`RleError::Format`] with the offset of the header byte if the input ends before
+///   `pixel_count` pixels were decoded ([`FormatError::Truncated`]), or if a packet extends
+///   past `pixel_count` ([`FormatError::Overrun`])
+///
+/// Complete packets before the error have been appended to `out`.
+///
+/// [`FormatError::Truncated`]: super::FormatError::Truncated
+/// [`FormatError::Overrun`]: super::FormatError::Overrun
+pub fn tga_rle_decode(
+    input: &[u8],
+    bytes_per_pixel: usize,
+    pixel_count: usize,
+    out: &mut Vec<u8>,
+) -> Result<usize, RleError> {
+    if bytes_per_pixel == 0 {
+        return Err(RleError::ZeroLookbehind);
+    }
+
+    let mut pos = 0;
+    let mut remaining = pixel_count;
+    while remaining > 0 {
+        let start = pos;
+        let header = *input.get(pos).ok_or(truncated(start))?;
+        pos += 1;
+        let count = usize::from(header & 0x7F) + 1;
+        if count > remaining {
+            return Err(overrun(start));
+        }
+
+        let is_run = header & 0x80 != 0;
+        let pixels = if is_run { 1 } else { count };
+        let packet_size = pixels.checked_mul(bytes_per_pixel).ok_or(RleError::CapacityOverflow)?;
+        let end = pos.checked_add(packet_size).ok_or(truncated(start))?;
+        let data = input.get(pos..end).ok_or(truncated(start))?;
+        let fill = if is_run { count - 1 } else { 0 };
+        let fill_bytes = fill.checked_mul(bytes_per_pixel).ok_or(RleError::CapacityOverflow)?;
+        pos = end;
+        out.extend_from_slice(data);
+        if is_run {
+            try_rle_decode(out, bytes_per_pixel, fill_bytes)?;
+        }
+        remaining -= count;
+    }
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_byte_pixels() {
+        let input = [0x83, 9, 0x00, 5, 0x81, 0];
+        let mut out = Vec::new();
+        assert_eq!(tga_rle_decode(&input, 1, 7, &mut out), Ok(6));
+        assert_eq!(out, &[9, 9, 9, 9, 5, 0, 0]);
+    }
+
+    #[test]
+    fn test_three_byte_pixels() {
+        // A run crossing from a 3 pixel scanline into the next one
+        let input = [0x01, 1, 2, 3, 4, 5, 6, 0x83, 10, 20, 30];
+        let mut out = Vec::new();
+        assert_eq!(tga_rle_decode(&input, 3, 6, &mut out), Ok(input.len()));
+        assert_eq!(out, &[1, 2, 3, 4, 5, 6, 10, 20, 30, 10, 20, 30, 10, 20, 30, 10, 20, 30]);
+    }
+
+    #[test]
+    fn test_four_byte_pixels() {
+        let mut input = vec![0xFF, 1, 2, 3, 4, 0x00, 5, 6, 7, 8];
+        input.extend_from_slice(b"next image");
+        let mut out = Vec::new();
+        assert_eq!(tga_rle_decode(&input, 4, 129, &mut out), Ok(10));
+        assert_eq!(out.len(), 129 * 4);
+        assert!(out[..512].chunks(4).all(|pixel| pixel == [1, 2, 3, 4]));
+        assert_eq!(&out[512..], &[5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut out = Vec::new();
+        assert_eq!(tga_rle_decode(&[0x80, 1], 0, 1, &mut out), Err(RleError::ZeroLookbehind));
+        assert_eq!(tga_rle_decode(&[0x81, 1, 2], 2, 2, &mut out), Ok(3));
+        assert_eq!(tga_rle_decode(&[0x81, 1], 2, 2, &mut out), Err(truncated(0)));
+        assert_eq!(tga_rle_decode(&[0x00, 1, 2], 2, 2, &mut out), Err(truncated(3)));
+        assert_eq!(tga_rle_decode(&[0x00, 1, 2, 0x81, 3, 4], 2, 2, &mut out), Err(overrun(3)));
+        assert_eq!(out, &[1, 2, 1, 2, 1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn test_huge_pixel_size() {
+        let half = usize::MAX / 2 + 1;
+        let mut out = Vec::new();
+        assert_eq!(tga_rle_decode(&[0x01], half, 2, &mut out), Err(RleError::CapacityOverflow));
+        assert_eq!(tga_rle_decode(&[0x81], half, 2, &mut out), Err(truncated(0)));
+        assert_eq!(tga_rle_decode(&[0x80], usize::MAX, 1, &mut out), Err(truncated(0)));
+        assert_eq!(tga_rle_decode(&[0x00], usize::MAX, 1, &mut out), Err(truncated(0)));
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use core::iter::FusedIterator;
+
+use crate::{check_fragment, decode_fail, wrap_index};
+
+/// Iterator over the items produced by repeating a lookbehind fragment
+///
+/// Created by [`rle_decode_iter`].
+#[derive(Debug, Clone)]
+pub struct RleIter<'a, T> {
+    lookbehind: &'a [T],
+    position: usize,
+    remaining: usize,
+}
+
+/// Lazily decodes run length encoded data, yielding one item at a time
+///
+/// Yields the `fill_length` items that [`rle_decode`](crate::rle_decode) would append after
+/// `lookbehind`, without allocating. The iterator knows its exact length, so `collect` allocates
+/// only once.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_iter;
+///
+/// let decoded: Vec<_> = rle_decode_iter(&[1, 2, 3], 7).collect();
+/// assert_eq!(decoded, &[1, 2, 3, 1, 2, 3, 1]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind` is empty
+pub fn rle_decode_iter<T>(lookbehind: &[T], fill_length: usize) -> RleIter<'_, T> where T: Copy {
+    if let Err(err) = check_fragment(lookbehind.len()) {
+        decode_fail(err);
+    }
+    RleIter {
+        lookbehind,
+        position: 0,
+        remaining: fill_length,
+    }
+}
+
+impl<'a, T> Iterator for RleIter<'a, T> where T: Copy {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let item = self.lookbehind[self.position];
+        self.position += 1;
+        if self.position == self.lookbehind.len() {
+            self.position = 0;
+        }
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<T> {
+        if n >= self.remaining {
+            self.remaining = 0;
+            return None;
+        }
+        // Both are smaller than the length, so the sum cannot overflow
+        self.position += wrap_index(n, self.lookbehind.len());
+        if self.position >= self.lookbehind.len() {
+            self.position -= self.lookbehind.len();
+        }
+        self.remaining -= n;
+        self.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for RleIter<'a, T> where T: Copy {}
+
+impl<'a, T> FusedIterator for RleIter<'a, T> where T: Copy {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::rle_decode;
+
+    #[test]
+    fn test_matches_rle_decode() {
+        let pattern = [3u8, 1, 4, 1, 5];
+        for lookbehind in 1..=pattern.len() {
+            for fill_length in 0..20 {
+                let iter = rle_decode_iter(&pattern[..lookbehind], fill_length);
+                assert_eq!(iter.len(), fill_length);
+                let decoded: Vec<_> = iter.collect();
+
+                let mut expected = pattern[..lookbehind].to_vec();
+                rle_decode(&mut expected, lookbehind, fill_length);
+                assert_eq!(decoded, expected[lookbehind..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_adapters() {
+        let mut iter = rle_decode_iter(&['a', 'b'], 5);
+        assert_eq!(iter.next(), Some('a'));
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        let zipped: Vec<_> = iter.zip(0..).take(3).collect();
+        assert_eq!(zipped, &[('b', 0), ('a', 1), ('b', 2)]);
+        assert_eq!(rle_decode_iter(&[0u64], usize::MAX).take(3).sum::<u64>(), 0);
+    }
+
+    #[test]
+    fn test_nth() {
+        let pattern = [3u8, 1, 4, 1, 5, 9, 2, 6];
+        for lookbehind in 1..=pattern.len() {
+            let expected: Vec<_> = rle_decode_iter(&pattern[..lookbehind], 50).collect();
+            for skip in 0..12 {
+                let skipped: Vec<_> =
+                    rle_decode_iter(&pattern[..lookbehind], 50).step_by(skip + 1).collect();
+                let stepped: Vec<_> = expected.iter().copied().step_by(skip + 1).collect();
+                assert_eq!(skipped, stepped);
+            }
+        }
+        let mut iter = rle_decode_iter(&[1, 2, 3, 4], usize::MAX);
+        assert_eq!(iter.nth(usize::MAX - 2), Some(2));
+        assert_eq!(iter.nth(1), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_empty_lookbehind() {
+        rle_decode_iter(&[] as &[u8], 0);
+    }
+}
@@ -14,143 +14,3629 @@
 //! rle_decode_helper::rle_decode(&mut decode_buffer, lookbehind_length, output_length);
 //! assert_eq!(decode_buffer, [0, 0, 1, 1, 0, 2, 3, 1, 0, 2, 3, 1, 0, 2, 3, 1, 0]);
 //! ```
+//!
+//! # Features
+//!
+//! * `std` (enabled by default): implements `std::error::Error` for [`RleError`] and adds the
+//!   `RleWriter` and `RleReader` adapters for `std::io`. Without it the crate is `#![no_std]`
+//!   and only needs the `alloc` crate.
+//! * `smallvec`: implements [`RleBuffer`] for `smallvec::SmallVec` and adds
+//!   `rle_decode_smallvec`.
+//! * `arrayvec`: implements [`RleBuffer`] for `arrayvec::ArrayVec` and adds
+//!   `rle_decode_arrayvec`, which reports output that does not fit instead of panicking.
+//! * `bytes`: implements [`RleBuffer`] for `bytes::BytesMut` and adds `rle_decode_bytes`.
+//! * `bitvec`: adds `rle_decode_bits`, which decodes bitmaps into a bit-packed
+//!   `bitvec::vec::BitVec`.
+//! * `serde`: implements `Serialize` and `Deserialize` for [`RleCommand`] and [`RleError`], so
+//!   command streams can be stored and loaded again.
+//! * `allocator_api`: implements [`RleBuffer`] for `Vec<T, A>` with any allocator `A`, so all
+//!   decode functions that are generic over the buffer accept it. Requires a nightly compiler.
+//! * `capi`: adds the `ffi` module with `extern "C"` functions. Requires `std`.
+//! * `simd`: repeats fragments of 2 to 16 bytes with wide stores instead of the doubling copy
+//!   loop, in all buffers of this crate. With `std` on x86, AVX stores are used if the CPU
+//!   supports them.
+//! * `stats`: adds `rle_decode_with_stats`, which counts the copies and items of a decode
+//!   session for profiling. The other decode functions are not affected.
+//! * `rayon`: adds `rle_decode_parallel`, which splits huge runs across the `rayon` thread
+//!   pool, and `rle_fill_many`, which fills many buffers at once. Requires `std`.
+//! * `prefetch`: issues software prefetches on the destination of fills of 64 KiB and more,
+//!   on `x86_64` only. Whether this helps depends on the hardware, so measure with the
+//!   `rle-huge-fill` benchmark before enabling it.
+//! * `tracing`: [`rle_decode`] enters a `tracing` debug span for fills of 65536 items and more,
+//!   with `lookbehind_length`, `fill_length` and the `elapsed_ns` of the fill as fields.
+//!   Smaller fills are not traced. Requires `std`.
+//!
+//! # WebAssembly
+//!
+//! All copies are done with `memcpy` and single item runs of one byte types with `memset`, so
+//! on `wasm32` they compile to the `memory.copy` and `memory.fill` instructions when the
+//! `bulk-memory` target feature is enabled. Recent compilers enable it by default, older ones
+//! need `-C target-feature=+bulk-memory`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+
+#[cfg(feature = "bitvec")]
+mod bits;
+mod buffer;
+mod command;
+mod decoder;
+mod encode;
+#[cfg(feature = "capi")]
+pub mod ffi;
+pub mod formats;
+mod iter;
+mod macros;
+mod options;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+mod prefetch;
+#[cfg(feature = "std")]
+mod reader;
+mod ring;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "std")]
+mod writer;
+
+pub use crate::buffer::RleBuffer;
+pub use crate::command::{
+    decode_commands, parse_commands, rle_decode_batch, rle_decode_stream, rle_encode_stream,
+    validate_commands, write_commands, RleCommand, RleSink,
+};
+pub use crate::encode::rle_encode;
+pub use crate::decoder::RleDecoder;
+pub use crate::iter::{rle_decode_iter, RleIter};
+pub use crate::options::RleOptions;
+pub use crate::ring::RleRingWindow;
+#[cfg(feature = "std")]
+pub use crate::reader::RleReader;
+#[cfg(feature = "std")]
+pub use crate::writer::RleWriter;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::{
+    rle_decode_parallel, rle_decode_parallel_with_threshold, rle_fill_many, PARALLEL_MIN_FILL,
+};
+#[cfg(feature = "stats")]
+pub use crate::stats::{rle_decode_with_stats, RleStats};
+#[cfg(feature = "smallvec")]
+pub use crate::buffer::rle_decode_smallvec;
+#[cfg(feature = "arrayvec")]
+pub use crate::buffer::rle_decode_arrayvec;
+#[cfg(feature = "bytes")]
+pub use crate::buffer::rle_decode_bytes;
+#[cfg(feature = "bitvec")]
+pub use crate::bits::rle_decode_bits;
 
-use std::{
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{
     ptr,
     ops,
     cmp,
+    fmt,
+    hash::{Hash, Hasher},
+    mem::{self, MaybeUninit},
 };
 
+/// Errors reported by the fallible decode functions
+///
+/// Only depends on `core`, so the fallible functions work the same under `#![no_std]`. The
+/// `std` feature adds the `std::error::Error` impl on top of `Display`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum RleError {
+    /// `lookbehind_length` was 0, so there is no fragment to repeat
+    ZeroLookbehind,
+    /// `lookbehind_length` is larger than the number of items in the buffer
+    LookbehindTooLarge {
+        lookbehind: usize,
+        buffer_len: usize,
+    },
+    /// The buffer is empty, so there is no fragment to repeat at all
+    EmptyBuffer,
+    /// `fill_length + buffer.len()` would overflow `usize`
+    CapacityOverflow,
+    /// The encoded input ended in the middle of a run
+    TruncatedInput,
+    /// The encoded input is not valid for its format
+    MalformedInput,
+    /// The buffer would grow beyond a length limit, like the one of [`rle_decode_limited`]
+    OutputTooLarge {
+        len: usize,
+        max_len: usize,
+    },
+    /// Memory for the output could not be allocated
+    Alloc,
+    /// The input of one of the [`formats`] decoders is corrupt
+    Format {
+        /// Offset of the packet that could not be decoded, from the start of the input
+        offset: usize,
+        error: formats::FormatError,
+    },
+    /// The source range of [`try_append_from_within`] is reversed, or reaches beyond the end
+    /// of the buffer
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    /// The decoded length differs from the fixed length of the output, like the array of
+    /// [`rle_decode_array`]
+    LengthMismatch {
+        len: usize,
+        expected: usize,
+    },
+    /// The fill range of [`rle_decode_slice_to`] overlaps the pattern range
+    Overlap {
+        pattern_start: usize,
+        pattern_end: usize,
+        fill_start: usize,
+        fill_end: usize,
+    },
+}
+
+impl fmt::Display for RleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RleError::ZeroLookbehind => f.write_str("attempt to repeat fragment of size 0"),
+            RleError::LookbehindTooLarge { lookbehind, buffer_len } => write!(
+                f,
+                "attempt to repeat fragment larger than buffer size ({} > {})",
+                lookbehind,
+                buffer_len,
+            ),
+            RleError::EmptyBuffer => f.write_str("attempt to repeat fragment of empty buffer"),
+            RleError::CapacityOverflow => f.write_str("capacity overflow"),
+            RleError::TruncatedInput => f.write_str("encoded input ended unexpectedly"),
+            RleError::MalformedInput => f.write_str("encoded input is malformed"),
+            RleError::OutputTooLarge { len, max_len } => write!(
+                f,
+                "decoded output exceeds length limit ({} > {})",
+                len,
+                max_len,
+            ),
+            RleError::Alloc => f.write_str("memory allocation failed"),
+            RleError::Format { offset, error } => {
+                write!(f, "malformed input at offset {}: {}", offset, error)
+            }
+            RleError::RangeOutOfBounds { start, end, len } => write!(
+                f,
+                "source range {}..{} is out of bounds of buffer of length {}",
+                start,
+                end,
+                len,
+            ),
+            RleError::LengthMismatch { len, expected } => write!(
+                f,
+                "decoded length does not match output length ({} != {})",
+                len,
+                expected,
+            ),
+            RleError::Overlap { pattern_start, pattern_end, fill_start, fill_end } => write!(
+                f,
+                "fill range {}..{} overlaps pattern range {}..{}",
+                fill_start,
+                fill_end,
+                pattern_start,
+                pattern_end,
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RleError {}
+
 /// Fast decoding of run length encoded data
 ///
 /// Takes the last `lookbehind_length` items of the buffer and repeatedly appends them until
 /// `fill_length` items have been copied.
 ///
+/// `lookbehind_length` may be equal to `buffer.len()`, in which case the whole buffer is
+/// repeated.
+///
+/// This is a thin wrapper around [`try_rle_decode`] for input that is known to be valid.
+/// Any [`RleBuffer`] can be decoded into, most commonly a `Vec<T>`.
+///
+/// `T` can be any `Copy` type, not only primitives: newtypes, types with niches like
+/// `NonZeroU32`, and structs with padding all work. Items are only ever copied whole, and the
+/// copies need no alignment beyond that of `T`.
+///
 /// # Panics
 /// * `lookbehind_length` is 0
-/// * `lookbehind_length` >= `buffer.len()`
+/// * `lookbehind_length` > `buffer.len()`
 /// * `fill_length + buffer.len()` would overflow
 #[inline(always)]
-pub fn rle_decode<T>(
+pub fn rle_decode<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy, B: RleBuffer<T> {
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
+    }
+
+    // Reserve space for *all* copies
+    buffer.reserve(fill_length);
+
+    #[cfg(feature = "tracing")]
+    {
+        if fill_length >= trace::TRACE_MIN_FILL {
+            unsafe {
+                // The lengths were validated above, and space for all copies was reserved
+                trace::rle_decode_traced(buffer, lookbehind_length, fill_length);
+            }
+            return;
+        }
+    }
+
+    unsafe {
+        // The lengths were validated above, and space for all copies was reserved
+        rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+    }
+}
+
+/// Fast decoding of run length encoded bytes, compiled once instead of at every call site
+///
+/// Same as [`rle_decode`] for a `Vec<u8>`, but never inlined: all callers share one copy of
+/// the decode loop, which keeps binaries small and shows up as a single symbol in profiles.
+/// [`rle_decode`] is always inlined, which lets the compiler specialize it for known lengths.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+#[inline(never)]
+pub fn rle_decode_bytes_outlined(
+    buffer: &mut Vec<u8>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) {
+    rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded data, with the fragment anywhere in the buffer
+///
+/// Repeats `buffer[pattern_start..(pattern_start + pattern_len)]` until `fill_length` items
+/// have been appended. With `pattern_start + pattern_len == buffer.len()`, this is the same as
+/// [`rle_decode`] with a lookbehind of `pattern_len`. The fragment does not have to be at the
+/// end of the buffer: after the first copy, the rest repeats the freshly appended items.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_at;
+///
+/// let mut buffer = vec![0, 1, 2, 3, 4, 5];
+/// rle_decode_at(&mut buffer, 1, 3, 7);
+/// assert_eq!(buffer, &[0, 1, 2, 3, 4, 5, 1, 2, 3, 1, 2, 3, 1]);
+/// ```
+///
+/// # Panics
+/// * `pattern_len` is 0
+/// * `pattern_start + pattern_len` > `buffer.len()`
+/// * `fill_length + buffer.len()` would overflow
+pub fn rle_decode_at<T>(
     buffer: &mut Vec<T>,
-    mut lookbehind_length: usize,
-    mut fill_length: usize,
+    pattern_start: usize,
+    pattern_len: usize,
+    fill_length: usize,
 ) where T: Copy {
-    if lookbehind_length == 0 {zero_repeat_fail()};
-
-    let copy_fragment_start = buffer.len()
-        .checked_sub(lookbehind_length)
-        .expect("attempt to repeat fragment larger than buffer size");
+    if let Err(err) = check_fragment(pattern_len) {
+        decode_fail(err);
+    }
+    let len = buffer.len();
+    match pattern_start.checked_add(pattern_len) {
+        Some(end) if end <= len => {}
+        end => decode_fail(RleError::RangeOutOfBounds {
+            start: pattern_start,
+            end: end.unwrap_or(usize::MAX),
+            len,
+        }),
+    }
+    if decoded_len(len, fill_length).is_none() {
+        decode_fail(RleError::CapacityOverflow);
+    }
 
-    // Reserve space for *all* copies
     buffer.reserve(fill_length);
 
-    while fill_length > 0 {
-        let fill_size = cmp::min(lookbehind_length, fill_length);
-        append_from_within(
-            buffer,
-            copy_fragment_start..(copy_fragment_start + fill_size)
-        );
-        fill_length -= fill_size;
-        lookbehind_length *= 2;
+    unsafe {
+        // The fragment is within the initialized items, so it cannot overlap the fill after
+        // them, and space for all copies was reserved above
+        rle_fill(buffer.as_mut_ptr(), pattern_start, pattern_len, len, fill_length);
+        buffer.set_len(len + fill_length);
     }
 }
 
-/// Copy of `vec::append_from_within()` proposed for inclusion in stdlib,
-/// see https://github.com/rust-lang/rfcs/pull/2714
-/// Heavily based on the implementation of `slice::copy_within()`,
-/// so we're pretty sure the implementation is sound
+/// Fast decoding of run length encoded data, treating a `lookbehind_length` of 0 as a no-op
+///
+/// Same as [`rle_decode`], but a copy with `lookbehind_length == 0` appends nothing instead of
+/// panicking, whatever `fill_length` is. Useful for encoders that emit empty back-references.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_lenient;
+///
+/// let mut buffer = vec![1, 2];
+/// rle_decode_lenient(&mut buffer, 0, 5);
+/// assert_eq!(buffer, &[1, 2]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` > `buffer.len()`
+/// * `fill_length + buffer.len()` would overflow
 #[inline(always)]
-fn append_from_within<T, R: ops::RangeBounds<usize>>(seif: &mut Vec<T>, src: R) where T: Copy, {
-    let src_start = match src.start_bound() {
-        ops::Bound::Included(&n) => n,
-        ops::Bound::Excluded(&n) => n
-            .checked_add(1)
-            .unwrap_or_else(|| vec_index_overflow_fail()),
-        ops::Bound::Unbounded => 0,
-    };
-    let src_end = match src.end_bound() {
-        ops::Bound::Included(&n) => n
-            .checked_add(1)
-            .unwrap_or_else(|| vec_index_overflow_fail()),
-        ops::Bound::Excluded(&n) => n,
-        ops::Bound::Unbounded => seif.len(),
+pub fn rle_decode_lenient<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy, B: RleBuffer<T> {
+    if lookbehind_length != 0 {
+        rle_decode(buffer, lookbehind_length, fill_length);
+    }
+}
+
+/// Fast decoding of run length encoded data, clamping fills that would not fit
+///
+/// Unlike [`rle_decode`], which panics if the new length would overflow, this appends as many
+/// of the `fill_length` items as a buffer can hold at all and returns how many that were. A
+/// buffer holds at most `usize::MAX` items, and at most `isize::MAX` bytes of them. For best
+/// effort decoding where cutting off an oversized run is acceptable.
+///
+/// The clamped fill is still allocated in full, so the allocation can fail as with
+/// [`rle_decode`] well before that limit.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_saturating;
+///
+/// let mut buffer = vec![(); 3];
+/// assert_eq!(rle_decode_saturating(&mut buffer, 1, usize::MAX), usize::MAX - 3);
+/// assert_eq!(buffer.len(), usize::MAX);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_length` > `buffer.len()`
+#[inline(always)]
+pub fn rle_decode_saturating<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> usize where T: Copy, B: RleBuffer<T> {
+    let fill_length = cmp::min(fill_length, max_fill::<T>(buffer.len()));
+    rle_decode(buffer, lookbehind_length, fill_length);
+    fill_length
+}
+
+/// Returns the most items that can be appended to a buffer of `len` items of `T`
+#[inline(always)]
+fn max_fill<T>(len: usize) -> usize {
+    let max_len = match mem::size_of::<T>() {
+        0 => usize::MAX,
+        size => isize::MAX as usize / size,
     };
-    assert!(src_start <= src_end, "src end is before src start");
-    assert!(src_end <= seif.len(), "src is out of bounds");
-    let count = src_end - src_start;
-    seif.reserve(count);
-    let vec_len = seif.len();
-    unsafe {
-        // This is safe because reserve() above succeeded,
-        // so `seif.len() + count` did not overflow usize
-        ptr::copy_nonoverlapping(
-            seif.get_unchecked(src_start),
-            seif.get_unchecked_mut(vec_len),
-            count,
-        );
-        seif.set_len(vec_len + count);
+    max_len.saturating_sub(len)
+}
+
+/// Fast decoding of run length encoded data, allowing a lookbehind longer than the buffer
+///
+/// Same as [`rle_decode`] as long as `lookbehind_length <= buffer.len()`. Where
+/// [`rle_decode`] panics because the fragment reaches before the start of the buffer, this
+/// fills the missing part of the fragment as it is produced: the whole buffer is repeated until
+/// it is `lookbehind_length` items long, and from then on the last `lookbehind_length` items,
+/// as usual. Item by item, the appended item at index `i` is a copy of the one at
+/// `i - lookbehind_length`, or at `i - initial_len` while `i < lookbehind_length`.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_modular;
+///
+/// let mut buffer = vec![1, 2, 3];
+/// rle_decode_modular(&mut buffer, 5, 9);
+/// assert_eq!(buffer, &[1, 2, 3, 1, 2, 1, 2, 3, 1, 2, 1, 2]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * the buffer is empty
+/// * `fill_length + buffer.len()` would overflow
+#[inline(always)]
+pub fn rle_decode_modular<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy, B: RleBuffer<T> {
+    let len = buffer.len();
+    if lookbehind_length <= len {
+        return rle_decode(buffer, lookbehind_length, fill_length);
+    }
+
+    if len == 0 {
+        decode_fail(RleError::EmptyBuffer);
+    }
+    if decoded_len(len, fill_length).is_none() {
+        decode_fail(RleError::CapacityOverflow);
+    }
+    buffer.reserve(fill_length);
+
+    // Complete the fragment by repeating everything there is, then continue as usual
+    let missing = cmp::min(lookbehind_length - len, fill_length);
+    rle_decode(buffer, len, missing);
+    if fill_length > missing {
+        rle_decode(buffer, lookbehind_length, fill_length - missing);
     }
 }
 
-// actually doesn't give any perf advantages, but we're keeping it
-// so we don't diverge from the proposed stdlib impl
-#[inline(never)]
-#[cold]
-fn vec_index_overflow_fail() -> ! {
-    panic!("attempted to index vec up to maximum usize");
+/// LZ77 style back-reference: appends `length` items, copied one by one from `distance` items
+/// back
+///
+/// The copy behaves as if it went item by item, so if `distance < length`, the source overlaps
+/// the destination and the items copied first are copied again later:
+///
+/// ```rust
+/// use rle_decode_helper::lz_copy_back;
+///
+/// let mut buffer = vec![1, 2, 3];
+/// lz_copy_back(&mut buffer, 2, 5);
+/// // 2, 3, then the 2, 3 that were just copied, then the first of those again
+/// assert_eq!(buffer, &[1, 2, 3, 2, 3, 2, 3, 2]);
+/// ```
+///
+/// This is the same operation as [`rle_decode`], described in the terms of LZ77 decoders.
+/// `rle_decode` thinks of its `lookbehind_length` as the size of a fragment at the end of the
+/// buffer that is repeated; an LZ77 back-reference starts `distance` items back and runs for
+/// `length` items, past the end of the buffer as it was. Repeating the last `distance` items is
+/// exactly what the item by item copy produces, so both are implemented by the same fast path,
+/// without copying item by item.
+///
+/// # Panics
+/// * `distance` is 0
+/// * `distance` > `buffer.len()`
+/// * `length + buffer.len()` would overflow
+#[inline(always)]
+pub fn lz_copy_back<T>(buffer: &mut Vec<T>, distance: usize, length: usize) where T: Copy {
+    rle_decode(buffer, distance, length)
 }
 
-// separating this into a function has measurable perf difference
-#[inline(never)]
-#[cold]
-fn zero_repeat_fail() -> ! {
-    panic!("attempt to repeat fragment of size 0");
+/// Fast decoding of run length encoded data, returning the number of appended items
+///
+/// Same as [`rle_decode`], but returns how many items were added to the buffer, which is always
+/// `fill_length`. Handy when driving a chunked parser or reporting progress.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+#[inline(always)]
+pub fn rle_decode_counted<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> usize where T: Copy, B: RleBuffer<T> {
+    rle_decode(buffer, lookbehind_length, fill_length);
+    fill_length
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// How far [`rle_decode_budgeted`] got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeProgress {
+    /// The whole fill was appended
+    Done,
+    /// The budget ran out; pass these back in to resume the fill where it stopped
+    Pending {
+        remaining_fill: usize,
+        /// The fragment to repeat next. It is a multiple of the original lookbehind length,
+        /// grown with the items already appended, so later calls copy larger chunks.
+        current_lookbehind: usize,
+    },
+}
 
-    #[test]
-    fn test_basic() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 3, 10);
-        assert_eq!(buf, &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+/// Fast decoding of run length encoded data, appending at most `max_elements_this_call` items
+///
+/// Lets a cooperative scheduler split up huge fills. Resuming with the returned state until
+/// [`DecodeProgress::Done`] produces exactly the output of a single [`rle_decode`] call.
+///
+/// Every call with a non-zero budget makes progress. A budget of 0 appends nothing and returns
+/// the state it was given for any non-empty fill, so a resume loop that keeps passing 0 never
+/// ends.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_budgeted, DecodeProgress};
+///
+/// let mut buffer = vec![1, 2];
+/// let mut state = (2, 9);
+/// while let DecodeProgress::Pending { remaining_fill, current_lookbehind } =
+///     rle_decode_budgeted(&mut buffer, state.0, state.1, 4)
+/// {
+///     state = (current_lookbehind, remaining_fill);
+/// }
+/// assert_eq!(buffer, &[1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1]);
+/// ```
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+pub fn rle_decode_budgeted<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+    max_elements_this_call: usize,
+) -> DecodeProgress where T: Copy, B: RleBuffer<T> {
+    let count = cmp::min(fill_length, max_elements_this_call);
+    rle_decode(buffer, lookbehind_length, count);
+    if count == fill_length {
+        return DecodeProgress::Done;
     }
-    
-    #[test]
-    fn test_zero_repeat() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 3, 0);
-        assert_eq!(buf, &[1, 2, 3, 4, 5]);
+    // The fragment and the items appended after it all repeat with a period of
+    // `lookbehind_length`, so any multiple of it that fits in there repeats the same way
+    DecodeProgress::Pending {
+        remaining_fill: fill_length - count,
+        current_lookbehind: (lookbehind_length + count) / lookbehind_length * lookbehind_length,
     }
-    
-    #[test]
-    #[should_panic]
-    fn test_zero_fragment() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 0, 10);
+}
+
+/// Fast decoding of run length encoded data, repeating the whole fragment `repeat_count` times
+///
+/// For formats that count repetitions instead of items: this appends
+/// `lookbehind_length * repeat_count` items, and is the same as calling [`rle_decode`] with
+/// that as the `fill_length`.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_repeat;
+///
+/// let mut buffer = vec![1, 2, 3];
+/// rle_decode_repeat(&mut buffer, 2, 3);
+/// assert_eq!(buffer, &[1, 2, 3, 2, 3, 2, 3, 2, 3]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length * repeat_count` would overflow
+/// * anything that makes [`rle_decode`] panic
+#[inline(always)]
+pub fn rle_decode_repeat<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    repeat_count: usize,
+) where T: Copy, B: RleBuffer<T> {
+    let fill_length = match lookbehind_length.checked_mul(repeat_count) {
+        Some(fill_length) => fill_length,
+        None => decode_fail(RleError::CapacityOverflow),
+    };
+    rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded data, returning the appended items
+///
+/// Same as [`rle_decode`], but returns the `fill_length` items that were added to the end of
+/// the buffer, so they can be processed without first remembering the previous length.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_get;
+///
+/// let mut buffer = vec![1, 2, 3];
+/// assert_eq!(rle_decode_get(&mut buffer, 2, 5), &[2, 3, 2, 3, 2]);
+/// ```
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+#[inline(always)]
+pub fn rle_decode_get<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> &[T] where T: Copy {
+    rle_decode(buffer, lookbehind_length, fill_length);
+    &buffer[(buffer.len() - fill_length)..]
+}
+
+/// Fast decoding of run length encoded data into a `Vec`, without over-allocating
+///
+/// Same as [`rle_decode`], but grows the `Vec` with [`Vec::reserve_exact`] instead of
+/// [`Vec::reserve`], so after a reallocation the capacity is just enough for the output.
+/// This saves memory when the buffer is not appended to afterwards, e.g. when a whole image is
+/// decoded in one go. Calling it in a loop of many small runs gives up the amortized growth of
+/// `reserve` and can reallocate on every call, so prefer [`rle_decode`] there.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_reserve_exact;
+///
+/// let mut buffer = vec![1, 2];
+/// rle_decode_reserve_exact(&mut buffer, 2, 98);
+/// assert_eq!(buffer.len(), 100);
+/// ```
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+#[inline(always)]
+pub fn rle_decode_reserve_exact<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy {
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
     }
-    
-    #[test]
-    #[should_panic]
-    fn test_zero_fragment_and_repeat() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 0, 0);
+
+    buffer.reserve_exact(fill_length);
+
+    unsafe {
+        // The lengths were validated above, and space for all copies was reserved
+        rle_decode_unchecked(buffer, lookbehind_length, fill_length);
     }
-    
-    #[test]
-    #[should_panic]
-    fn test_overflow_fragment() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 10, 10);
+}
+
+/// Fast decoding of run length encoded data into the spare capacity of a `Vec`
+///
+/// Repeats the last `lookbehind_length` items until the `Vec` is full, appending
+/// `buffer.capacity() - buffer.len()` items, and returns that count. This is the "fill as much
+/// as fits" run of some formats, for a buffer that was sized up front; it never reallocates.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_to_capacity;
+///
+/// let mut buffer = Vec::with_capacity(10);
+/// buffer.extend_from_slice(&[1, 2, 3]);
+/// let capacity = buffer.capacity();
+/// assert_eq!(rle_decode_to_capacity(&mut buffer, 2), capacity - 3);
+/// assert_eq!(buffer.len(), capacity);
+/// assert_eq!(buffer[..7], [1, 2, 3, 2, 3, 2, 3]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_length` > `buffer.len()`
+#[inline(always)]
+pub fn rle_decode_to_capacity<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+) -> usize where T: Copy {
+    let fill_length = buffer.capacity() - buffer.len();
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
     }
-    
-    #[test]
-    #[should_panic]
-    fn test_overflow_buf_size() {
-        let mut buf = vec![1, 2, 3, 4, 5];
-        rle_decode(&mut buf, 4, usize::max_value());
+
+    unsafe {
+        // The lengths were validated above, and the fill is exactly the spare capacity
+        rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+    }
+    fill_length
+}
+
+/// Fast decoding of run length encoded data, reporting invalid input instead of panicking
+///
+/// Behaves exactly like [`rle_decode`], but returns an [`RleError`] for input that would make
+/// `rle_decode` panic, and for allocation failures that would make it abort. Use this when the
+/// lengths come from untrusted data.
+///
+/// Either exactly `fill_length` items are appended, or none at all: every check happens before
+/// the buffer is modified, so if an error is returned, the buffer still has its previous
+/// contents and capacity.
+///
+/// # Errors
+/// * [`RleError::ZeroLookbehind`] if `lookbehind_length` is 0
+/// * [`RleError::EmptyBuffer`] if the buffer is empty
+/// * [`RleError::LookbehindTooLarge`] if `lookbehind_length` is larger than `buffer.len()`
+/// * [`RleError::CapacityOverflow`] if `fill_length + buffer.len()` would overflow, or the fill
+///   would take more than `isize::MAX` bytes
+/// * [`RleError::Alloc`] if the memory for the output could not be reserved, see
+///   [`RleBuffer::try_reserve`]
+#[inline(always)]
+pub fn try_rle_decode<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T> {
+    fragment_start(buffer.len(), lookbehind_length, fill_length)?;
+    check_fill_bytes::<T>(fill_length)?;
+
+    // Reserve space for *all* copies, without aborting if the allocation fails
+    buffer.try_reserve(fill_length)?;
+
+    unsafe {
+        // The lengths were validated above, and space for all copies was reserved
+        rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+    }
+    Ok(())
+}
+
+/// Decoding of run length encoded data that appends exactly `fill_length` items, or nothing
+///
+/// For formats where the declared fill length must be satisfiable, and anything else means the
+/// input is corrupt. Every precondition is checked before the buffer is touched, so a failed
+/// call leaves it exactly as it was, contents and capacity.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_exact, RleError};
+///
+/// let mut buffer = vec![1, 2, 3];
+/// assert_eq!(rle_decode_exact(&mut buffer, 4, 2), Err(RleError::LookbehindTooLarge {
+///     lookbehind: 4,
+///     buffer_len: 3,
+/// }));
+/// assert_eq!(buffer, &[1, 2, 3]);
+/// rle_decode_exact(&mut buffer, 2, 3).unwrap();
+/// assert_eq!(buffer, &[1, 2, 3, 2, 3, 2]);
+/// ```
+///
+/// # Errors
+/// The same as [`try_rle_decode`].
+#[inline(always)]
+pub fn rle_decode_exact<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T> {
+    try_rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded data, with the fill given as `repeat_count` strides of
+/// `fragment_stride` items
+///
+/// For formats that express fills as counts: appends `repeat_count * fragment_stride` items
+/// like [`try_rle_decode`], but reports an overflow of the product as an error instead of
+/// leaving it to the caller.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_checked_fill, RleError};
+///
+/// let mut buffer = vec![1, 2, 3];
+/// rle_decode_checked_fill(&mut buffer, 2, 3, 2).unwrap();
+/// assert_eq!(buffer, &[1, 2, 3, 2, 3, 2, 3, 2, 3]);
+/// assert_eq!(
+///     rle_decode_checked_fill(&mut buffer, 2, usize::MAX, 2),
+///     Err(RleError::CapacityOverflow),
+/// );
+/// ```
+///
+/// The buffer is left untouched if an error is returned.
+///
+/// # Errors
+/// * [`RleError::CapacityOverflow`] if `repeat_count * fragment_stride` would overflow
+/// * any error of [`try_rle_decode`]
+#[inline(always)]
+pub fn rle_decode_checked_fill<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    repeat_count: usize,
+    fragment_stride: usize,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T> {
+    let fill_length = repeat_count.checked_mul(fragment_stride).ok_or(RleError::CapacityOverflow)?;
+    try_rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded text, keeping the `String` valid UTF-8
+///
+/// Repeats the last `lookbehind_length_bytes` bytes of `s` like [`try_rle_decode`], as long as
+/// the result is valid UTF-8. That is the case if the fragment starts at a char boundary, and
+/// the fill ends at one, i.e. after a whole number of chars of the fragment.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_str;
+///
+/// let mut text = String::from("ab🦀");
+/// rle_decode_str(&mut text, 5, 10).unwrap();
+/// assert_eq!(text, "ab🦀b🦀b🦀");
+/// assert!(rle_decode_str(&mut text, 5, 3).is_err());
+/// ```
+///
+/// # Errors
+/// * [`RleError::MalformedInput`] if the fragment would start, or the fill would end, in the
+///   middle of a multibyte char
+/// * any error of [`try_rle_decode`]
+///
+/// The string is left untouched if an error is returned.
+pub fn rle_decode_str(
+    s: &mut String,
+    lookbehind_length_bytes: usize,
+    fill_length_bytes: usize,
+) -> Result<(), RleError> {
+    let start = fragment_start(s.len(), lookbehind_length_bytes, fill_length_bytes)?;
+    if !s.is_char_boundary(start) {
+        return Err(RleError::MalformedInput);
+    }
+    // Every repetition of the fragment starts at a char boundary, so only the last one can be
+    // cut short in the middle of a char
+    if !s[start..].is_char_boundary(fill_length_bytes % lookbehind_length_bytes) {
+        return Err(RleError::MalformedInput);
+    }
+    unsafe {
+        // The appended bytes are whole copies of the fragment, which is valid UTF-8, followed
+        // by a prefix of it that ends at a char boundary
+        try_rle_decode(s.as_mut_vec(), lookbehind_length_bytes, fill_length_bytes)
+    }
+}
+
+/// Fast decoding of run length encoded data, without any checks
+///
+/// Same as [`rle_decode`], but the lengths are not validated and capacity is not reserved.
+/// This is meant for inner loops that validate the input and reserve space for many runs up
+/// front.
+///
+/// # Safety
+/// * `lookbehind_length` is not 0
+/// * `lookbehind_length <= buffer.len()`
+/// * capacity for at least `fill_length` more items was set aside by an earlier call to
+///   [`RleBuffer::reserve`], and not used up since
+#[inline(always)]
+pub unsafe fn rle_decode_unchecked<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy, B: RleBuffer<T> {
+    buffer.repeat_tail_unchecked(lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded data into a `Vec`, without any code path that panics
+///
+/// The same as [`rle_decode_unchecked`], restricted to `Vec` so that no [`RleBuffer`]
+/// implementation outside of this crate is involved. With debug assertions disabled, the
+/// decode loop contains no checks, allocation or indexing at all. This is verified in the test
+/// suite with the `no-panic` crate, by `cargo test --release`. It only covers builds without
+/// the `simd` and `prefetch` features, whose copy loops are chosen at runtime.
+///
+/// Validate the input with [`validate_commands`] or [`decoded_len`], and reserve space for it
+/// up front, for example with [`Vec::try_reserve`].
+///
+/// # Safety
+/// * `lookbehind_length` is not 0
+/// * `lookbehind_length <= buffer.len()`
+/// * `buffer.capacity() - buffer.len() >= fill_length`
+///
+/// With debug assertions enabled, some of these are checked, and violating them panics.
+#[inline(always)]
+pub unsafe fn rle_decode_nopanic<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy {
+    buffer.repeat_tail_unchecked(lookbehind_length, fill_length)
+}
+
+/// The portable decode loop behind [`RleBuffer::repeat_tail_unchecked`]
+///
+/// # Safety
+/// Same as [`RleBuffer::repeat_tail_unchecked`].
+#[inline(always)]
+unsafe fn repeat_by_doubling<T, B>(
+    buffer: &mut B,
+    mut lookbehind_length: usize,
+    mut fill_length: usize,
+) where B: RleBuffer<T> + ?Sized {
+    if lookbehind_length == 1 {
+        // The buffer has at least one item, since `lookbehind_length <= buffer.len()`
+        buffer.extend_from_last_unchecked(fill_length);
+        return;
+    }
+
+    // `lookbehind_length` is the size of the repeated region at the end of the buffer, which
+    // starts at `copy_fragment_start`, so the source is in bounds. It doubles until the rest
+    // fits into one copy. The caller reserved space for all copies.
+    let copy_fragment_start = buffer.len() - lookbehind_length;
+    while fill_length > lookbehind_length {
+        buffer.extend_from_within_unchecked(copy_fragment_start, lookbehind_length);
+        fill_length -= lookbehind_length;
+        // Cannot overflow, that is the length of the buffer from `copy_fragment_start` on
+        lookbehind_length *= 2;
+    }
+    buffer.extend_from_within_unchecked(copy_fragment_start, fill_length);
+}
+
+/// Fast decoding of run length encoded data with a cap on the buffer length
+///
+/// Like [`try_rle_decode`], but refuses to grow the buffer beyond `max_total_len` items in total,
+/// counting what is already in it. The limit is checked before anything is allocated, so a
+/// maliciously large `fill_length` cannot exhaust memory. The buffer is left untouched if an
+/// error is returned.
+///
+/// # Errors
+/// * [`RleError::OutputTooLarge`] if `buffer.len() + fill_length` exceeds `max_total_len`
+/// * everything [`try_rle_decode`] reports
+#[inline(always)]
+pub fn rle_decode_limited<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+    max_total_len: usize,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T> {
+    fragment_start(buffer.len(), lookbehind_length, fill_length)?;
+    // Cannot overflow, fragment_start() checked it
+    let len = buffer.len() + fill_length;
+    if len > max_total_len {
+        return Err(RleError::OutputTooLarge { len, max_len: max_total_len });
+    }
+    try_rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Fast decoding of run length encoded data that cannot reach back before `boundary`
+///
+/// For buffers holding several independent streams one after another: `boundary` is the index
+/// where the current stream starts, and the lookbehind has to stay within
+/// `buffer[boundary..]`. Otherwise the same as [`try_rle_decode`].
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_bounded, RleError};
+///
+/// let mut buffer = vec![1, 2, 3, 4, 5];
+/// // The current stream is `[4, 5]`
+/// rle_decode_bounded(&mut buffer, 3, 2, 3).unwrap();
+/// assert_eq!(buffer, &[1, 2, 3, 4, 5, 4, 5, 4]);
+/// assert_eq!(
+///     rle_decode_bounded(&mut buffer, 3, 6, 1),
+///     Err(RleError::LookbehindTooLarge { lookbehind: 6, buffer_len: 5 }),
+/// );
+/// ```
+///
+/// # Errors
+/// * [`RleError::EmptyBuffer`] if the current stream is empty
+/// * [`RleError::LookbehindTooLarge`] if `lookbehind_length` is larger than the current stream,
+///   `buffer.len() - boundary`
+/// * everything [`try_rle_decode`] reports
+///
+/// The buffer is left untouched if an error is returned.
+///
+/// # Panics
+/// * `boundary > buffer.len()`
+#[inline(always)]
+pub fn rle_decode_bounded<T, B>(
+    buffer: &mut B,
+    boundary: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T> {
+    assert!(boundary <= buffer.len(), "stream boundary is beyond the end of the buffer");
+    let stream_len = buffer.len() - boundary;
+    if lookbehind_length != 0 {
+        // Checks the lookbehind against the stream alone, the fill is checked below
+        fragment_start(stream_len, lookbehind_length, 0)?;
+    }
+    try_rle_decode(buffer, lookbehind_length, fill_length)
+}
+
+/// Returns the buffer length after appending `fill_length` items, or `None` on overflow
+///
+/// The decode functions fail with [`RleError::CapacityOverflow`] when this returns `None`, and
+/// [`try_rle_decode`] also when the fill would take more than `isize::MAX` bytes. Being a
+/// `const fn`, it can also size arrays at compile time:
+///
+/// ```rust
+/// use rle_decode_helper::decoded_len;
+///
+/// const LEN: usize = match decoded_len(4, 12) {
+///     Some(len) => len,
+///     None => panic!(),
+/// };
+/// let buffer = [0u8; LEN];
+/// assert_eq!(buffer.len(), 16);
+/// ```
+#[inline]
+pub const fn decoded_len(current_len: usize, fill_length: usize) -> Option<usize> {
+    current_len.checked_add(fill_length)
+}
+
+/// Returns the sizes of the copies, in items, that [`rle_decode`] makes to fill a `Vec`
+///
+/// Runs no copies, it only replays the decisions of the decode loop, for tests and to show how
+/// the copies double: the first copy comes from the fragment, and every later one repeats all
+/// that was appended so far. A single item fragment is filled in one pass, and fills of a few
+/// items are copied item by item. Zero sized items and the `simd` and `prefetch` features copy
+/// differently.
+///
+/// ```rust
+/// use rle_decode_helper::fill_schedule;
+///
+/// assert_eq!(fill_schedule(3, 20), [3, 3, 6, 8]);
+/// assert_eq!(fill_schedule(1, 20), [20]);
+/// assert_eq!(fill_schedule(3, 4), [1, 1, 1, 1]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+pub fn fill_schedule(lookbehind_length: usize, fill_length: usize) -> Vec<usize> {
+    if let Err(err) = check_fragment(lookbehind_length) {
+        decode_fail(err);
+    }
+    let mut schedule = Vec::new();
+    if fill_length == 0 {
+        return schedule;
+    }
+    // The same decisions as `rle_fill` and `repeat_bytes`
+    if lookbehind_length == 1 {
+        schedule.push(fill_length);
+        return schedule;
+    }
+    if fill_length <= SMALL_FILL {
+        schedule.resize(fill_length, 1);
+        return schedule;
+    }
+    let first = cmp::min(lookbehind_length, fill_length);
+    schedule.push(first);
+    let rest = fill_length - first;
+    if rest > 0 {
+        let mut lookbehind = lookbehind_length;
+        let mut written = 0;
+        while rest - written > lookbehind {
+            schedule.push(lookbehind);
+            written += lookbehind;
+            lookbehind *= 2;
+        }
+        schedule.push(rest - written);
+    }
+    schedule
+}
+
+/// Decoding of run length encoded data into a separate output buffer
+///
+/// Takes the last `lookbehind_length` items of `source` and appends `fill_length` items to `out`
+/// by repeating them.
+///
+/// Unlike [`rle_decode`], the lookbehind data is not read from the buffer that is written to.
+/// Only the first fragment is copied from `source`. After that, repetition continues from the
+/// freshly written tail of `out`, just like a growing LZ77 window. The result is the same as
+/// appending `source` to `out`, calling [`rle_decode`] and removing `source` again.
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_length` > `source.len()`
+/// * `fill_length + out.len()` would overflow
+pub fn rle_decode_into<T>(
+    source: &[T],
+    lookbehind_length: usize,
+    fill_length: usize,
+    out: &mut Vec<T>,
+) where T: Copy {
+    let copy_fragment_start = match fragment_start(source.len(), lookbehind_length, 0) {
+        Ok(start) => start,
+        Err(err) => decode_fail(err),
+    };
+    if out.len().checked_add(fill_length).is_none() {
+        decode_fail(RleError::CapacityOverflow);
+    }
+
+    out.reserve(fill_length);
+
+    let first_fragment = cmp::min(lookbehind_length, fill_length);
+    out.extend_from_slice(&source[copy_fragment_start..(copy_fragment_start + first_fragment)]);
+    if fill_length > first_fragment {
+        rle_decode(out, lookbehind_length, fill_length - first_fragment);
+    }
+}
+
+/// Appends `fill_length` items to `out` by repeating `pattern`
+///
+/// The fill primitive without the lookbehind: `pattern` can be any slice, not just the tail of
+/// the output, which suits background fills or generated test data. After the call,
+/// `out[old_len + i] == pattern[i % pattern.len()]` for every appended item.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_with_pattern;
+///
+/// let mut out = vec![0];
+/// rle_decode_with_pattern(&[1, 2, 3], 7, &mut out);
+/// assert_eq!(out, &[0, 1, 2, 3, 1, 2, 3, 1]);
+/// ```
+///
+/// # Panics
+/// * `pattern` is empty
+/// * `fill_length + out.len()` would overflow
+pub fn rle_decode_with_pattern<T>(
+    pattern: &[T],
+    fill_length: usize,
+    out: &mut Vec<T>,
+) where T: Copy {
+    if let [item] = *pattern {
+        let new_len = match decoded_len(out.len(), fill_length) {
+            Some(len) => len,
+            None => decode_fail(RleError::CapacityOverflow),
+        };
+        out.resize(new_len, item);
+        return;
+    }
+    rle_decode_into(pattern, pattern.len(), fill_length, out);
+}
+
+/// Appends `fill_length` items repeating `pattern` across two `Vec`s
+///
+/// Fills `first` until it holds `first_cap` items, and appends the rest to `second`, as if both
+/// were a single output: the pattern continues in `second` where it stopped in `first`. With
+/// `first` already at `first_cap` items or more, everything goes to `second`.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_scatter;
+///
+/// let mut header = vec![0];
+/// let mut body = Vec::new();
+/// rle_decode_scatter(&[1, 2, 3], 7, &mut header, 3, &mut body);
+/// assert_eq!(header, &[0, 1, 2]);
+/// assert_eq!(body, &[3, 1, 2, 3, 1]);
+/// ```
+///
+/// # Panics
+/// * `pattern` is empty
+/// * the part of the fill that goes to `second`, plus `second.len()`, would overflow
+///
+/// Neither `Vec` is modified if it panics.
+pub fn rle_decode_scatter<T>(
+    pattern: &[T],
+    fill_length: usize,
+    first: &mut Vec<T>,
+    first_cap: usize,
+    second: &mut Vec<T>,
+) where T: Copy {
+    if let Err(err) = check_fragment(pattern.len()) {
+        decode_fail(err);
+    }
+    let first_fill = cmp::min(fill_length, first_cap.saturating_sub(first.len()));
+    let second_fill = fill_length - first_fill;
+    if decoded_len(second.len(), second_fill).is_none() {
+        decode_fail(RleError::CapacityOverflow);
+    }
+
+    rle_decode_with_pattern(pattern, first_fill, first);
+
+    // Start `second` with the pattern rotated to where `first` stopped, then repeat that
+    second.reserve(second_fill);
+    let seam = first_fill % pattern.len();
+    let head = cmp::min(second_fill, pattern.len() - seam);
+    second.extend_from_slice(&pattern[seam..(seam + head)]);
+    let wrapped = cmp::min(second_fill - head, seam);
+    second.extend_from_slice(&pattern[..wrapped]);
+    if second_fill > pattern.len() {
+        rle_decode(second, pattern.len(), second_fill - pattern.len());
+    }
+}
+
+/// Decodes into a scratch `Vec`, replacing its contents and reusing its allocation
+///
+/// Overwrites `scratch` with `lookbehind` followed by `fill_length` items repeating it. Whatever
+/// `scratch` held before is dropped, but its capacity is kept, so in a double buffering loop
+/// that reuses the same `Vec` for every frame, no allocation happens once it has grown to the
+/// largest frame.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_reuse;
+///
+/// let mut scratch = vec![9; 16];
+/// rle_decode_reuse(&mut scratch, &[1, 2], 3);
+/// assert_eq!(scratch, &[1, 2, 1, 2, 1]);
+/// assert!(scratch.capacity() >= 16);
+/// ```
+///
+/// # Panics
+/// * `lookbehind` is empty
+/// * `lookbehind.len() + fill_length` would overflow
+///
+/// `scratch` is left untouched if it panics.
+pub fn rle_decode_reuse<T>(
+    scratch: &mut Vec<T>,
+    lookbehind: &[T],
+    fill_length: usize,
+) where T: Copy {
+    if let Err(err) = fragment_start(lookbehind.len(), lookbehind.len(), fill_length) {
+        decode_fail(err)
+    }
+    scratch.clear();
+    // Both parts at once, so a reallocation never copies the pattern
+    scratch.reserve(lookbehind.len() + fill_length);
+    scratch.extend_from_slice(lookbehind);
+    unsafe {
+        // The lengths were validated above, and space for all copies was reserved
+        rle_decode_unchecked(scratch, lookbehind.len(), fill_length);
+    }
+}
+
+/// Decoding of run length encoded data with a preset dictionary
+///
+/// Same as [`rle_decode`], but the lookbehind may reach back past the start of `buffer` into
+/// `dict`, as if `dict` was prepended to `buffer`. This is the preset dictionary of zlib and
+/// similar formats. Once the repeated fragment has been copied across the seam, repetition
+/// continues from the tail of `buffer` as usual, so `dict` is never modified.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_with_dict;
+///
+/// let mut buffer = vec![3, 4];
+/// rle_decode_with_dict(&[1, 2], &mut buffer, 3, 5);
+/// assert_eq!(buffer, &[3, 4, 2, 3, 4, 2, 3]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_length` > `dict.len() + buffer.len()`
+/// * `fill_length + buffer.len()` would overflow
+pub fn rle_decode_with_dict<T>(
+    dict: &[T],
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy {
+    if lookbehind_length <= buffer.len() {
+        rle_decode(buffer, lookbehind_length, fill_length);
+        return;
+    }
+
+    let buffer_len = buffer.len();
+    let from_dict = lookbehind_length - buffer_len;
+    let dict_start = match dict.len().checked_sub(from_dict) {
+        Some(start) => start,
+        None => decode_fail(RleError::LookbehindTooLarge {
+            lookbehind: lookbehind_length,
+            buffer_len: dict.len() + buffer_len,
+        }),
+    };
+    if decoded_len(buffer_len, fill_length).is_none() {
+        decode_fail(RleError::CapacityOverflow);
+    }
+
+    buffer.reserve(fill_length);
+
+    // The first fragment is the end of `dict` followed by all of `buffer`
+    let dict_part = cmp::min(from_dict, fill_length);
+    buffer.extend_from_slice(&dict[dict_start..(dict_start + dict_part)]);
+    let buffer_part = cmp::min(buffer_len, fill_length - dict_part);
+    unsafe {
+        // `buffer_part <= buffer_len`, and space for all copies was reserved above
+        append_from_within_unchecked(buffer, 0, buffer_part);
+    }
+
+    let rest = fill_length - dict_part - buffer_part;
+    if rest > 0 {
+        // A whole fragment has been copied, so the tail of `buffer` can be repeated from here on
+        rle_decode(buffer, lookbehind_length, rest);
+    }
+}
+
+/// Decoding of run length encoded data inside a caller provided slice
+///
+/// `buf[lookbehind_start..(lookbehind_start + lookbehind_length)]` holds the lookbehind data. The
+/// next `fill_length` items of `buf` are overwritten by repeating it, the same way
+/// [`rle_decode`] appends to a `Vec`. Nothing is allocated, so this works on any memory, for
+/// example an `mmap`'d file.
+///
+/// Returns the number of items written, which is always `fill_length`.
+///
+/// The fill comes right after the lookbehind, so the two cannot overlap. To fill anywhere else
+/// in the slice, use [`rle_decode_slice_to`].
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_start + lookbehind_length + fill_length` is larger than `buf.len()`
+pub fn rle_decode_slice<T>(
+    buf: &mut [T],
+    lookbehind_start: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> usize where T: Copy {
+    if let Err(err) = check_fragment(lookbehind_length) {
+        decode_fail(err);
+    }
+    let fill_start = lookbehind_start.checked_add(lookbehind_length);
+    let fill_end = fill_start.and_then(|start| start.checked_add(fill_length));
+    let dest = match (fill_start, fill_end) {
+        (Some(start), Some(end)) if end <= buf.len() => start,
+        _ => slice_too_small_fail(buf.len()),
+    };
+
+    unsafe {
+        // The lookbehind and the fill were checked to be in bounds above, and the fill starts
+        // where the lookbehind ends
+        rle_fill(buf.as_mut_ptr(), lookbehind_start, lookbehind_length, dest, fill_length);
+    }
+    fill_length
+}
+
+/// Repeats a pattern from anywhere in a slice into a destination range of the same slice
+///
+/// Overwrites `buf[dest_start..(dest_start + fill_length)]` with `fill_length` items repeating
+/// `buf[pattern]`, like [`rle_decode_slice`] but without requiring the fill to follow the
+/// pattern. The copies read the pattern while the fill is written, so the two ranges must not
+/// overlap; they may be adjacent, in either order. This is checked in all builds.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_slice_to, RleError};
+///
+/// let mut buf = [0, 0, 0, 0, 0, 1, 2];
+/// assert_eq!(rle_decode_slice_to(&mut buf, 5..7, 0, 5), Ok(5));
+/// assert_eq!(buf, [1, 2, 1, 2, 1, 1, 2]);
+/// assert_eq!(
+///     rle_decode_slice_to(&mut buf, 0..2, 1, 3),
+///     Err(RleError::Overlap { pattern_start: 0, pattern_end: 2, fill_start: 1, fill_end: 4 }),
+/// );
+/// ```
+///
+/// Returns the number of items written, which is always `fill_length`.
+///
+/// # Errors
+/// * [`RleError::ZeroLookbehind`] if `pattern` is empty
+/// * [`RleError::RangeOutOfBounds`] if `pattern` or the fill range is reversed or reaches
+///   beyond the end of `buf`
+/// * [`RleError::Overlap`] if the fill range overlaps `pattern`
+///
+/// Nothing is written if an error is returned.
+pub fn rle_decode_slice_to<T>(
+    buf: &mut [T],
+    pattern: ops::Range<usize>,
+    dest_start: usize,
+    fill_length: usize,
+) -> Result<usize, RleError> where T: Copy {
+    let len = buf.len();
+    if pattern.start > pattern.end {
+        return Err(RleError::RangeOutOfBounds { start: pattern.start, end: pattern.end, len });
+    }
+    check_fragment(pattern.len())?;
+    if pattern.end > len {
+        return Err(RleError::RangeOutOfBounds { start: pattern.start, end: pattern.end, len });
+    }
+    let dest_end = match dest_start.checked_add(fill_length) {
+        Some(end) if end <= len => end,
+        end => {
+            return Err(RleError::RangeOutOfBounds {
+                start: dest_start,
+                end: end.unwrap_or(usize::MAX),
+                len,
+            });
+        }
+    };
+    if fill_length == 0 {
+        return Ok(0);
+    }
+    if dest_start < pattern.end && pattern.start < dest_end {
+        return Err(RleError::Overlap {
+            pattern_start: pattern.start,
+            pattern_end: pattern.end,
+            fill_start: dest_start,
+            fill_end: dest_end,
+        });
+    }
+
+    unsafe {
+        // Both ranges were checked to be in bounds and disjoint above
+        rle_fill(buf.as_mut_ptr(), pattern.start, pattern.len(), dest_start, fill_length);
+    }
+    Ok(fill_length)
+}
+
+/// Overwrites a region of a fixed size buffer with a repeated pattern from the same buffer
+///
+/// Writes `fill_length` items repeating `buffer[pattern_start..(pattern_start + pattern_len)]`
+/// to `buffer[dest_start..(dest_start + fill_length)]`, the in place counterpart of
+/// [`rle_decode_at`] for buffers that never grow, like a framebuffer. Same as
+/// [`rle_decode_slice_to`] with the pattern given by its start and length.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_overwrite;
+///
+/// let mut frame = [1, 2, 0, 0, 0, 0, 0, 9];
+/// rle_decode_overwrite(&mut frame, 0, 2, 3, 4).unwrap();
+/// assert_eq!(frame, [1, 2, 0, 1, 2, 1, 2, 9]);
+/// ```
+///
+/// # Errors
+/// The same as [`rle_decode_slice_to`], where `pattern_start + pattern_len` overflowing
+/// is reported as [`RleError::RangeOutOfBounds`]. Nothing is written if an error is returned.
+pub fn rle_decode_overwrite<T>(
+    buffer: &mut [T],
+    pattern_start: usize,
+    pattern_len: usize,
+    dest_start: usize,
+    fill_length: usize,
+) -> Result<(), RleError> where T: Copy {
+    let pattern_end = pattern_start.checked_add(pattern_len).ok_or(RleError::RangeOutOfBounds {
+        start: pattern_start,
+        end: usize::MAX,
+        len: buffer.len(),
+    })?;
+    rle_decode_slice_to(buffer, pattern_start..pattern_end, dest_start, fill_length)?;
+    Ok(())
+}
+
+/// Decoding of run length encoded data into uninitialized memory
+///
+/// Fills all of `fill` by repeating `lookbehind`, without requiring the destination to be
+/// initialized first. `fill` is only ever written to, or copied from after it was written, so
+/// its previous contents are never read.
+///
+/// Returns the number of items written, which is always `fill.len()`. All of them are
+/// initialized afterwards, so it is sound for the caller to `assume_init` that range.
+///
+/// # Panics
+/// * `lookbehind` is empty
+pub fn rle_decode_uninit<T>(
+    lookbehind: &[T],
+    fill: &mut [MaybeUninit<T>],
+) -> usize where T: Copy {
+    if let Err(err) = check_fragment(lookbehind.len()) {
+        decode_fail(err);
+    }
+
+    let first_fragment = cmp::min(lookbehind.len(), fill.len());
+    for (dst, &src) in fill[..first_fragment].iter_mut().zip(lookbehind) {
+        *dst = MaybeUninit::new(src);
+    }
+
+    // `fill[..written]` is always a whole number of fragments, so it can be repeated as is
+    let mut written = first_fragment;
+    while written < fill.len() {
+        let fill_size = cmp::min(written, fill.len() - written);
+        let (src, dst) = fill.split_at_mut(written);
+        dst[..fill_size].copy_from_slice(&src[..fill_size]);
+        written += fill_size;
+    }
+    written
+}
+
+/// Decoding of run length encoded data into a fixed size array
+///
+/// Returns an array that starts with `lookbehind`, followed by `fill_length` items repeating
+/// it, for decoding on the stack. The array is written once, without being zeroed first.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_array, RleError};
+///
+/// assert_eq!(rle_decode_array(&[1, 2], 3), Ok([1, 2, 1, 2, 1]));
+/// assert_eq!(
+///     rle_decode_array::<u8, 4>(&[1, 2], 3),
+///     Err(RleError::LengthMismatch { len: 5, expected: 4 }),
+/// );
+/// ```
+///
+/// # Errors
+/// * [`RleError::ZeroLookbehind`] if `lookbehind` is empty
+/// * [`RleError::CapacityOverflow`] if `lookbehind.len() + fill_length` would overflow
+/// * [`RleError::LengthMismatch`] if `lookbehind.len() + fill_length` is not `N`
+pub fn rle_decode_array<T, const N: usize>(
+    lookbehind: &[T],
+    fill_length: usize,
+) -> Result<[T; N], RleError> where T: Copy {
+    check_fragment(lookbehind.len())?;
+    let len = decoded_len(lookbehind.len(), fill_length).ok_or(RleError::CapacityOverflow)?;
+    if len != N {
+        return Err(RleError::LengthMismatch { len, expected: N });
+    }
+
+    let mut out = [MaybeUninit::<T>::uninit(); N];
+    let (head, tail) = out.split_at_mut(lookbehind.len());
+    for (dst, &src) in head.iter_mut().zip(lookbehind) {
+        *dst = MaybeUninit::new(src);
+    }
+    rle_decode_uninit(lookbehind, tail);
+    unsafe {
+        // All `N` items were initialized above, and `[MaybeUninit<T>; N]` has the same layout
+        // as `[T; N]`
+        Ok(ptr::read(out.as_ptr().cast::<[T; N]>()))
+    }
+}
+
+/// Decoding of run length encoded data into a fixed size array, at compile time
+///
+/// The `const fn` counterpart of [`rle_decode_array`], for lookup tables: returns `pattern`
+/// followed by `fill_length` items repeating it. It copies item by item, so at run time
+/// [`rle_decode_array`] is faster.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_const;
+///
+/// const DATA: [u8; 10] = rle_decode_const(&[1, 2, 3], 7);
+/// assert_eq!(DATA, [1, 2, 3, 1, 2, 3, 1, 2, 3, 1]);
+/// ```
+///
+/// In a `const` item, a wrong `N` is reported when compiling:
+///
+/// ```compile_fail
+/// use rle_decode_helper::rle_decode_const;
+///
+/// const DATA: [u8; 9] = rle_decode_const(&[1, 2, 3], 7);
+/// ```
+///
+/// # Panics
+/// * `pattern` is empty
+/// * `pattern.len() + fill_length` is not `N`
+pub const fn rle_decode_const<T, const N: usize>(
+    pattern: &[T],
+    fill_length: usize,
+) -> [T; N] where T: Copy {
+    if check_fragment(pattern.len()).is_err() {
+        panic!("attempt to repeat fragment of size 0");
+    }
+    match pattern.len().checked_add(fill_length) {
+        Some(len) if len == N => {}
+        _ => panic!("decoded length does not match output length"),
+    }
+
+    let mut out = [pattern[0]; N];
+    let mut i = 1;
+    while i < N {
+        out[i] = pattern[i % pattern.len()];
+        i += 1;
+    }
+    out
+}
+
+/// Decoding of run length encoded data into a caller managed region of memory
+///
+/// Treats `region[..initialized_len]` as the decoded data so far, and writes the fill right
+/// after it, like [`try_rle_decode`] does for a `Vec`. Nothing is allocated, so the caller
+/// keeps full control over the memory, for example a window handed out by an arena.
+///
+/// Returns the new initialized length, `initialized_len + fill_length`. On success, exactly
+/// `region[initialized_len..(initialized_len + fill_length)]` was written and is initialized,
+/// and the rest of `region` is untouched. If an error is returned, nothing was written.
+///
+/// ```rust
+/// use std::mem::MaybeUninit;
+/// use rle_decode_helper::rle_decode_in_place;
+///
+/// let mut region = [MaybeUninit::<u8>::uninit(); 8];
+/// region[0] = MaybeUninit::new(1);
+/// region[1] = MaybeUninit::new(2);
+/// let len = unsafe { rle_decode_in_place(&mut region, 2, 2, 5) }.unwrap();
+/// assert_eq!(len, 7);
+/// let decoded: Vec<u8> = region[..len].iter().map(|item| unsafe { item.assume_init() }).collect();
+/// assert_eq!(decoded, &[1, 2, 1, 2, 1, 2, 1]);
+/// ```
+///
+/// # Errors
+/// * [`RleError::OutputTooLarge`] if the fill does not fit into `region`
+/// * [`RleError::ZeroLookbehind`], [`RleError::EmptyBuffer`],
+///   [`RleError::LookbehindTooLarge`] and [`RleError::CapacityOverflow`] like
+///   [`try_rle_decode`], with `initialized_len` as the buffer length
+///
+/// # Safety
+/// * `region[..initialized_len]` is initialized
+///
+/// # Panics
+/// * `initialized_len` is larger than `region.len()`
+pub unsafe fn rle_decode_in_place<T>(
+    region: &mut [MaybeUninit<T>],
+    initialized_len: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<usize, RleError> where T: Copy {
+    assert!(initialized_len <= region.len(), "initialized length is beyond the end of the region");
+    let copy_fragment_start = fragment_start(initialized_len, lookbehind_length, fill_length)?;
+    // Cannot overflow, fragment_start() checked it
+    let len = initialized_len + fill_length;
+    if len > region.len() {
+        return Err(RleError::OutputTooLarge { len, max_len: region.len() });
+    }
+
+    // The pattern is initialized as promised by the caller, and the fill fits into the region
+    // right after it
+    rle_fill(
+        region.as_mut_ptr().cast::<T>(),
+        copy_fragment_start,
+        lookbehind_length,
+        initialized_len,
+        fill_length,
+    );
+    Ok(len)
+}
+
+/// Decoding of run length encoded data for output that grows towards the front
+///
+/// The mirror image of [`rle_decode`]: prepends `fill_length` items to `buffer`, so that every
+/// new item repeats the one `lookbehind_length` items after it. The first `lookbehind_length`
+/// items are the fragment, and the fill ends with a whole copy of it.
+///
+/// ```rust
+/// use std::collections::VecDeque;
+/// use rle_decode_helper::rle_decode_front;
+///
+/// let mut buffer: VecDeque<_> = vec![1, 2, 3].into();
+/// rle_decode_front(&mut buffer, 2, 5);
+/// assert_eq!(buffer, &[2, 1, 2, 1, 2, 1, 2, 3]);
+/// ```
+///
+/// The fill is appended and then rotated to the front, which moves at most `fill_length`
+/// items, so the whole call takes time linear in `fill_length`.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`].
+pub fn rle_decode_front<T>(
+    buffer: &mut VecDeque<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy {
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
+    }
+
+    // The fill has to end with the whole fragment, so when read from the start it begins
+    // with the last `fill_length % lookbehind_length` items of it
+    let split = lookbehind_length - fill_length % lookbehind_length;
+    let mut fragment: Vec<T> = Vec::with_capacity(lookbehind_length);
+    fragment.extend(buffer.range(split..lookbehind_length));
+    fragment.extend(buffer.range(..split));
+
+    buffer.reserve(fill_length);
+    rle_decode_chunks(&fragment, fill_length, |chunk| buffer.extend(chunk));
+    buffer.rotate_right(fill_length);
+}
+
+/// Decoding of run length encoded data into a callback, without any output buffer
+///
+/// Calls `emit` with consecutive chunks that together make up the `fill_length` items produced
+/// by repeating `lookbehind`. Since the repeated output is periodic, every chunk is borrowed
+/// straight from `lookbehind` and nothing is buffered.
+///
+/// The chunks are exactly `fill_length / lookbehind.len()` copies of the whole `lookbehind`,
+/// followed by `lookbehind[..fill_length % lookbehind.len()]` if that is not empty. `emit` is
+/// never called with an empty slice.
+///
+/// ```rust
+/// use rle_decode_helper::rle_decode_chunks;
+///
+/// let mut chunks = Vec::new();
+/// rle_decode_chunks(&[1, 2, 3], 7, |chunk| chunks.push(chunk.to_vec()));
+/// assert_eq!(chunks, [vec![1, 2, 3], vec![1, 2, 3], vec![1]]);
+/// ```
+///
+/// # Panics
+/// * `lookbehind` is empty
+pub fn rle_decode_chunks<T, F>(
+    lookbehind: &[T],
+    fill_length: usize,
+    mut emit: F,
+) where T: Copy, F: FnMut(&[T]) {
+    if let Err(err) = check_fragment(lookbehind.len()) {
+        decode_fail(err);
+    }
+
+    for _ in 0..(fill_length / lookbehind.len()) {
+        emit(lookbehind);
+    }
+    let remainder = fill_length % lookbehind.len();
+    if remainder > 0 {
+        emit(&lookbehind[..remainder]);
+    }
+}
+
+/// Feeds the decoded items into a hasher, without materializing them
+///
+/// Hashes the `fill_length` items produced by repeating `lookbehind`, in order. Like
+/// [`rle_decode_chunks`], the output is never buffered: every chunk of it is hashed with
+/// [`Hash::hash_slice`] straight from `lookbehind`.
+///
+/// The result equals `Hash::hash_slice(&decoded, hasher)` for hashers that only depend on the
+/// sequence of bytes written to them, not on how it was split across calls. That includes the
+/// `DefaultHasher` of `std` and most checksums. Hashing a `Vec` of the items gives a different
+/// result, since it also hashes the length.
+///
+/// ```rust
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::{Hash, Hasher};
+/// use rle_decode_helper::rle_decode_hash;
+///
+/// let mut streamed = DefaultHasher::new();
+/// rle_decode_hash(b"abc", 7, &mut streamed);
+/// let mut whole = DefaultHasher::new();
+/// Hash::hash_slice(b"abcabca", &mut whole);
+/// assert_eq!(streamed.finish(), whole.finish());
+/// ```
+///
+/// # Panics
+/// * `lookbehind` is empty
+pub fn rle_decode_hash<T, H>(
+    lookbehind: &[T],
+    fill_length: usize,
+    hasher: &mut H,
+) where T: Copy + Hash, H: Hasher {
+    rle_decode_chunks(lookbehind, fill_length, |chunk| Hash::hash_slice(chunk, hasher));
+}
+
+/// Decoding of run length encoded data for types that are `Clone` but not `Copy`
+///
+/// Same semantics as [`rle_decode`], but every appended item is produced by calling `clone()`
+/// on its source item instead of copying memory. This is slower than the `Copy` path, but works
+/// for types that own resources, like a `Box`.
+///
+/// If a `clone()` call panics, all items cloned up to that point stay in the buffer, so the
+/// buffer never exposes uninitialized or double-dropped items.
+///
+/// # Panics
+/// * `lookbehind_length` is 0
+/// * `lookbehind_length` > `buffer.len()`
+/// * `fill_length + buffer.len()` would overflow
+/// * a `clone()` call panics
+pub fn rle_decode_clone<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Clone {
+    let copy_fragment_start = match fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        Ok(start) => start,
+        Err(err) => decode_fail(err),
+    };
+
+    buffer.reserve(fill_length);
+
+    let base = buffer.as_mut_ptr();
+    let mut guard = SetLenOnDrop {
+        len: buffer.len(),
+        vec: buffer,
+    };
+    for i in 0..fill_length {
+        unsafe {
+            // The source is always an already initialized item: item `len + i` repeats
+            // item `copy_fragment_start + i`, which is at most item `len + i - 1`.
+            // The destination is in bounds because of the reserve() above.
+            let item = (*base.add(copy_fragment_start + i)).clone();
+            ptr::write(base.add(guard.len), item);
+        }
+        guard.len += 1;
+    }
+}
+
+/// Writes `count` copies of `value` starting at `dst`
+///
+/// Used for single item runs, where the doubling copy would need `log2(count)` separate copies.
+/// For one byte types the loop is recognized as a `memset`.
+///
+/// # Safety
+/// `dst` must be valid for writes of `count` items
+#[inline(always)]
+unsafe fn fill_single<T>(dst: *mut T, value: T, count: usize) where T: Copy {
+    for i in 0..count {
+        dst.add(i).write(value);
+    }
+}
+
+/// Returns `index % len`, using a mask instead of the division if `len` is a power of two
+///
+/// Window sizes and pixel sizes are usually powers of two, and a division is many times
+/// slower than the check.
+#[inline(always)]
+fn wrap_index(index: usize, len: usize) -> usize {
+    if len.is_power_of_two() {
+        index & (len - 1)
+    } else {
+        index % len
+    }
+}
+
+/// Longer fills of `rle_fill!` with a literal pattern are done by [`rle_decode_with_pattern`]
+///
+/// The stores of the unrolled pattern beat the doubling copy for short fills, but not for long
+/// ones. Measured with the `rle-fill-macro` benchmark, with a pattern of 3 bytes: the stores
+/// are 20% faster at 64 items, on par at 128 and take a quarter longer at 256.
+#[doc(hidden)]
+pub const __MACRO_FILL_MAX: usize = 64;
+
+/// Fills of at most this many items are copied item by item in [`rle_fill`]
+///
+/// Below it, the calls to `memcpy` cost more than they save. Measured with the
+/// `rle-small-fill` benchmark, with a lookbehind of 3 bytes: the loop is faster for fills of up
+/// to 8 items, but already takes a third longer than the copies at 16.
+const SMALL_FILL: usize = 8;
+
+/// Fills `base[dest_start..(dest_start + fill_len)]` by repeating the pattern
+/// `base[pattern_start..(pattern_start + pattern_len)]`
+///
+/// The one primitive all buffers with direct access to their storage decode with; appending to
+/// a buffer of `len` items is `rle_fill(base, len - lookbehind_length, lookbehind_length, len,
+/// count)`. Apart from single item and short fills, all work happens on bytes in
+/// [`repeat_bytes`], so the loop is compiled once instead of for every item type and call site.
+/// Zero sized items need no work at all. [`fill_schedule`] replays these decisions, and has to
+/// be kept in sync.
+///
+/// # Safety
+/// * `pattern_len` is not 0
+/// * the pattern is initialized, and the destination is valid for writes
+/// * both are within the same allocation, and do not overlap
+#[inline(always)]
+unsafe fn rle_fill<T>(
+    base: *mut T,
+    pattern_start: usize,
+    pattern_len: usize,
+    dest_start: usize,
+    fill_len: usize,
+) where T: Copy {
+    debug_assert!(pattern_len != 0);
+    debug_assert!(
+        pattern_start + pattern_len <= dest_start || dest_start + fill_len <= pattern_start
+    );
+    if mem::size_of::<T>() == 0 {
+        // Zero sized items have no bytes to copy. The pattern is not empty, so `T` is inhabited
+        // and the destination already holds valid items.
+        return;
+    }
+    let pattern = base.add(pattern_start);
+    let dest = base.add(dest_start);
+    if fill_len == 1 {
+        // Common in LZ streams. For one byte types, `fill_single` would call `memset` for it:
+        // in `rle-small-fill` with a lookbehind of 1, 1000 such fills took 4.5 us instead of 1.7
+        dest.write(*pattern);
+        return;
+    }
+    if pattern_len == 1 {
+        fill_single(dest, *pattern, fill_len);
+        return;
+    }
+    if fill_len <= SMALL_FILL {
+        // Every item repeats the one `pattern_len` items back, which is either in the pattern
+        // or was just written
+        for i in 0..fill_len {
+            let src = if i < pattern_len { pattern.add(i) } else { dest.add(i - pattern_len) };
+            dest.add(i).write(*src);
+        }
+        return;
+    }
+    // The first repetition always comes from the pattern, the rest from the destination, so
+    // they do not have to be next to each other.
+    let first = cmp::min(pattern_len, fill_len);
+    ptr::copy_nonoverlapping(pattern, dest, first);
+    if fill_len > first {
+        // Both products are within the allocation, so they cannot overflow. Copying wide items
+        // as `T` instead, at their own alignment, measured no faster in `rle-item-size`.
+        let size = mem::size_of::<T>();
+        repeat_bytes(dest.add(first).cast(), pattern_len * size, (fill_len - first) * size);
+    }
+}
+
+/// Writes `fill_bytes` bytes to `dst` by repeating the `lookbehind_bytes` bytes right before it
+///
+/// The bytes are only ever moved, so this is correct for items of any `Copy` type.
+///
+/// # Safety
+/// * the `lookbehind_bytes` bytes before `dst` are readable
+/// * `fill_bytes` bytes starting at `dst` are writable
+/// * `lookbehind_bytes` is not 0, unless `fill_bytes` is 0 too
+unsafe fn repeat_bytes(dst: *mut MaybeUninit<u8>, mut lookbehind_bytes: usize, fill_bytes: usize) {
+    #[cfg(feature = "simd")]
+    {
+        if simd::is_supported(lookbehind_bytes, fill_bytes) {
+            simd::repeat_pattern(dst, lookbehind_bytes, fill_bytes);
+            return;
+        }
+    }
+    #[cfg(all(feature = "prefetch", target_arch = "x86_64"))]
+    {
+        if prefetch::is_supported(fill_bytes) {
+            prefetch::repeat_bytes_prefetch(dst, lookbehind_bytes, fill_bytes);
+            return;
+        }
+    }
+
+    // `src..(dst + written)` is always a whole number of fragments, `lookbehind_bytes` long, so
+    // copying its start continues the pattern. It doubles until the rest fits into one copy.
+    // Source and destination never overlap: an overlapping `memmove` (`copy_within`) would not
+    // repeat the pattern anyway, and measured slower on 1 MiB fills when used for these copies.
+    let src = dst.sub(lookbehind_bytes);
+    let mut written = 0;
+    while fill_bytes - written > lookbehind_bytes {
+        ptr::copy_nonoverlapping(src, dst.add(written), lookbehind_bytes);
+        written += lookbehind_bytes;
+        // Cannot overflow, that is the size of `src..(dst + written)` within the allocation
+        lookbehind_bytes *= 2;
+    }
+    ptr::copy_nonoverlapping(src, dst.add(written), fill_bytes - written);
+}
+
+/// Updates the length of a `Vec` when dropped, so items written so far are kept on unwind
+struct SetLenOnDrop<'a, T> {
+    vec: &'a mut Vec<T>,
+    len: usize,
+}
+
+impl<'a, T> Drop for SetLenOnDrop<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            // Every item up to `len` was written before `len` was incremented
+            self.vec.set_len(self.len);
+        }
+    }
+}
+
+/// Checks that there is a fragment to repeat
+///
+/// Shared by the decode functions, so an empty fragment or pattern is always reported as
+/// [`RleError::ZeroLookbehind`], whatever the fill length and before any bounds are checked.
+/// The fallible functions return it, the others panic with it.
+#[inline(always)]
+const fn check_fragment(fragment_len: usize) -> Result<(), RleError> {
+    if fragment_len == 0 {
+        return Err(RleError::ZeroLookbehind);
+    }
+    Ok(())
+}
+
+/// Checks that a fill of `fill_length` items takes at most `isize::MAX` bytes
+///
+/// No allocation or copy may span more than that. `try_reserve` would catch it too, but as an
+/// allocation error for large `T`, so the fallible functions check it first to report
+/// [`RleError::CapacityOverflow`] instead.
+#[inline(always)]
+fn check_fill_bytes<T>(fill_length: usize) -> Result<(), RleError> {
+    let fill_bytes = fill_length.checked_mul(mem::size_of::<T>());
+    if fill_bytes.filter(|&bytes| bytes <= isize::MAX as usize).is_none() {
+        return Err(RleError::CapacityOverflow);
+    }
+    Ok(())
+}
+
+/// Validates the decode arguments and returns the index where the repeated fragment starts
+#[inline(always)]
+fn fragment_start(
+    buffer_len: usize,
+    lookbehind_length: usize,
+    fill_length: usize,
+) -> Result<usize, RleError> {
+    check_fragment(lookbehind_length)?;
+    if buffer_len == 0 {
+        return Err(RleError::EmptyBuffer);
+    }
+
+    let copy_fragment_start = buffer_len
+        .checked_sub(lookbehind_length)
+        .ok_or(RleError::LookbehindTooLarge {
+            lookbehind: lookbehind_length,
+            buffer_len,
+        })?;
+
+    if decoded_len(buffer_len, fill_length).is_none() {
+        return Err(RleError::CapacityOverflow);
+    }
+    Ok(copy_fragment_start)
+}
+
+/// Appends a copy of the items in the range `src` to the end of the vector
+///
+/// This is `Vec::append_from_within()` as proposed for inclusion in stdlib,
+/// see <https://github.com/rust-lang/rfcs/pull/2714>.
+/// Heavily based on the implementation of `slice::copy_within()`,
+/// so we're pretty sure the implementation is sound.
+///
+/// ```rust
+/// use rle_decode_helper::append_from_within;
+///
+/// let mut buf = vec![1, 2, 3, 4];
+/// append_from_within(&mut buf, 1..=2);
+/// assert_eq!(buf, &[1, 2, 3, 4, 2, 3]);
+/// ```
+///
+/// # Panics
+/// * the range ends before it starts
+/// * the range ends after the end of the vector
+/// * either end of the range is `usize::MAX` and inclusive (or exclusive, for the start)
+/// * the new capacity overflows or the allocation fails
+#[inline(always)]
+pub fn append_from_within<T, R: ops::RangeBounds<usize>>(seif: &mut Vec<T>, src: R) where T: Copy, {
+    let src_start = match src.start_bound() {
+        ops::Bound::Included(&n) => n,
+        ops::Bound::Excluded(&n) => n
+            .checked_add(1)
+            .unwrap_or_else(|| vec_index_overflow_fail()),
+        ops::Bound::Unbounded => 0,
+    };
+    let src_end = match src.end_bound() {
+        ops::Bound::Included(&n) => n
+            .checked_add(1)
+            .unwrap_or_else(|| vec_index_overflow_fail()),
+        ops::Bound::Excluded(&n) => n,
+        ops::Bound::Unbounded => seif.len(),
+    };
+    // Both checks come before the reservation, so a bogus range cannot cause a huge allocation
+    assert!(src_start <= src_end, "src end is before src start");
+    assert!(src_end <= seif.len(), "src is out of bounds");
+    let count = src_end - src_start;
+    seif.reserve(count);
+    unsafe {
+        // This is safe because reserve() above succeeded,
+        // so `seif.len() + count` did not overflow usize
+        append_from_within_unchecked(seif, src_start, count);
+    }
+}
+
+/// Appends a copy of the items in the range `src` to the end of the buffer, reporting errors
+/// instead of panicking
+///
+/// The fallible counterpart of [`append_from_within`], for any [`RleBuffer`]. The range is
+/// validated before anything is reserved, and the buffer is left untouched if an error is
+/// returned.
+///
+/// ```rust
+/// use rle_decode_helper::{try_append_from_within, RleError};
+///
+/// let mut buf = vec![1, 2, 3, 4];
+/// try_append_from_within(&mut buf, 1..=2).unwrap();
+/// assert_eq!(buf, &[1, 2, 3, 4, 2, 3]);
+/// assert_eq!(
+///     try_append_from_within(&mut buf, 5..7),
+///     Err(RleError::RangeOutOfBounds { start: 5, end: 7, len: 6 }),
+/// );
+/// ```
+///
+/// # Errors
+/// * [`RleError::RangeOutOfBounds`] if the range ends before it starts, or after the end of the
+///   buffer. An inclusive end of `usize::MAX` is reported as `usize::MAX`, as is an exclusive
+///   start of it.
+/// * [`RleError::CapacityOverflow`] if the new length would overflow `usize`
+/// * [`RleError::Alloc`] if the memory for the copy could not be reserved, see
+///   [`RleBuffer::try_reserve`]
+pub fn try_append_from_within<T, B, R>(
+    buffer: &mut B,
+    src: R,
+) -> Result<(), RleError> where T: Copy, B: RleBuffer<T>, R: ops::RangeBounds<usize> {
+    let len = buffer.len();
+    let (start, start_overflow) = match src.start_bound() {
+        ops::Bound::Included(&n) => (n, false),
+        ops::Bound::Excluded(&n) => n.checked_add(1).map_or((n, true), |n| (n, false)),
+        ops::Bound::Unbounded => (0, false),
+    };
+    let (end, end_overflow) = match src.end_bound() {
+        ops::Bound::Included(&n) => n.checked_add(1).map_or((n, true), |n| (n, false)),
+        ops::Bound::Excluded(&n) => (n, false),
+        ops::Bound::Unbounded => (len, false),
+    };
+    if start_overflow || end_overflow || start > end || end > len {
+        return Err(RleError::RangeOutOfBounds { start, end, len });
+    }
+
+    let count = end - start;
+    if decoded_len(len, count).is_none() {
+        return Err(RleError::CapacityOverflow);
+    }
+    buffer.try_reserve(count)?;
+    unsafe {
+        // The range is within the buffer, and space for the copy was reserved above
+        buffer.extend_from_within_unchecked(start, count);
+    }
+    Ok(())
+}
+
+/// [`append_from_within`] without the bounds checks and the reservation
+///
+/// The decode loop reserves space for all copies up front, so checking again on every
+/// iteration is wasted work.
+///
+/// # Safety
+/// * `src_start + count <= seif.len()`
+/// * `seif.capacity() - seif.len() >= count`
+#[inline(always)]
+unsafe fn append_from_within_unchecked<T>(seif: &mut Vec<T>, src_start: usize, count: usize) where T: Copy {
+    let vec_len = seif.len();
+    // The destination starts at `vec_len`, so a source that ends there cannot overlap it
+    debug_assert!(src_start.checked_add(count).is_some_and(|src_end| src_end <= vec_len));
+    debug_assert!(seif.capacity() - vec_len >= count);
+    let base = seif.as_mut_ptr();
+    ptr::copy_nonoverlapping(
+        base.add(src_start),
+        base.add(vec_len),
+        count,
+    );
+    seif.set_len(vec_len + count);
+}
+
+// actually doesn't give any perf advantages, but we're keeping it
+// so we don't diverge from the proposed stdlib impl
+#[inline(never)]
+#[cold]
+fn vec_index_overflow_fail() -> ! {
+    panic!("attempted to index vec up to maximum usize");
+}
+
+#[inline(never)]
+#[cold]
+fn slice_too_small_fail(len: usize) -> ! {
+    panic!("lookbehind and fill region do not fit in a slice of length {}", len);
+}
+
+// separating this into a function has measurable perf difference
+#[inline(never)]
+#[cold]
+fn decode_fail(err: RleError) -> ! {
+    panic!("{}", err);
+}
+
+/// xorshift, good enough to generate test data without extra dependencies
+#[cfg(test)]
+fn xorshift(seed: &mut u32) -> u32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 17;
+    *seed ^= *seed << 5;
+    *seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 3, 10);
+        assert_eq!(buf, &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+    }
+    
+    #[test]
+    fn test_fill_one() {
+        // Only the first item of the fragment is appended, whatever its length
+        let initial: Vec<u32> = (10..30).collect();
+        for lookbehind in 1..=initial.len() {
+            let mut buf = initial.clone();
+            rle_decode(&mut buf, lookbehind, 1);
+            assert_eq!(buf[..20], initial[..]);
+            assert_eq!(buf[20..], [initial[20 - lookbehind]]);
+
+            let mut bytes: Vec<u8> = (0..20).collect();
+            assert_eq!(try_rle_decode(&mut bytes, lookbehind, 1), Ok(()));
+            assert_eq!(bytes.len(), 21);
+            assert_eq!(bytes[20], 20 - lookbehind as u8);
+
+            let mut out = vec![0];
+            rle_decode_with_pattern(&initial[..lookbehind], 1, &mut out);
+            assert_eq!(out, &[0, 10]);
+        }
+        assert_eq!(fill_schedule(5, 1), [1]);
+    }
+
+    #[test]
+    fn test_zero_repeat() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 3, 0);
+        assert_eq!(buf, &[1, 2, 3, 4, 5]);
+    }
+    
+    #[test]
+    #[should_panic]
+    fn test_zero_fragment() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 0, 10);
+    }
+    
+    #[test]
+    #[should_panic]
+    fn test_zero_fragment_and_repeat() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 0, 0);
+    }
+    
+    #[test]
+    #[should_panic]
+    fn test_overflow_fragment() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 10, 10);
+    }
+    
+    #[test]
+    #[should_panic]
+    fn test_overflow_buf_size() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut buf, 4, usize::MAX);
+    }
+
+    #[test]
+    fn test_lookbehind_equals_len() {
+        let mut buf = vec![1, 2, 3];
+        rle_decode(&mut buf, 3, 7);
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_huge_lookbehind() {
+        // Zero sized items allow a buffer long enough to overflow the doubled lookbehind. The
+        // loop is called directly, since `rle_fill` returns early for them. A `Vec<()>` has a
+        // capacity of `usize::MAX`, so nothing has to be reserved.
+        let half = usize::MAX / 2 + 1;
+        let mut buf = vec![(); half];
+        unsafe { repeat_by_doubling(&mut buf, half, 1) };
+        assert_eq!(buf.len(), half + 1);
+
+        let mut buf = vec![(); half];
+        unsafe { repeat_by_doubling(&mut buf, half - 1, half - 1) };
+        assert_eq!(buf.len(), usize::MAX);
+
+        // Doubles until the lookbehind is more than a quarter of the address space
+        let mut buf = vec![(); 3];
+        unsafe { repeat_by_doubling(&mut buf, 3, usize::MAX - 3) };
+        assert_eq!(buf.len(), usize::MAX);
+    }
+
+    #[test]
+    fn test_copy_wrappers() {
+        use core::num::{NonZeroU16, Wrapping};
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        #[repr(transparent)]
+        struct Id(NonZeroU16);
+
+        // Padding between and after the fields
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Pair(u8, u32, u8);
+
+        fn check<T>(pattern: &[T]) where T: Copy + PartialEq + core::fmt::Debug {
+            for lookbehind in 1..=pattern.len() {
+                for fill in [0, 1, 7, 64, 100] {
+                    let mut decoded = pattern.to_vec();
+                    rle_decode(&mut decoded, lookbehind, fill);
+                    let fragment = &pattern[(pattern.len() - lookbehind)..];
+                    let expected: Vec<T> = pattern
+                        .iter()
+                        .chain(fragment.iter().cycle().take(fill))
+                        .copied()
+                        .collect();
+                    assert_eq!(decoded, expected);
+                }
+            }
+        }
+
+        check(&[Wrapping(1u32), Wrapping(u32::MAX), Wrapping(7)]);
+        let ids: Vec<Id> = (1..=5).map(|i| Id(NonZeroU16::new(i * 1000).unwrap())).collect();
+        check(&ids);
+        check(&[Some(ids[0]), None, Some(ids[4])]);
+        check(&[Pair(1, 2, 3), Pair(4, 5, 6), Pair(u8::MAX, u32::MAX, 0)]);
+    }
+
+    /// Checks every lookbehind and fill length up to a few fragments against a naive repeat
+    fn check_item_type<T>(make: impl Fn(u8) -> T) where T: Copy + PartialEq + fmt::Debug {
+        let items: Vec<T> = (1..=7).map(&make).collect();
+        for lookbehind in 1..=items.len() {
+            for fill in 0..40 {
+                let mut buf = items.clone();
+                rle_decode(&mut buf, lookbehind, fill);
+                assert_eq!(buf.len(), items.len() + fill);
+                for i in items.len()..buf.len() {
+                    assert_eq!(buf[i], buf[i - lookbehind], "{} {}", lookbehind, fill);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_items() {
+        check_item_type(|i| u32::from(i) * 0x0101_0101);
+        check_item_type(|i| [i; 4]);
+        check_item_type(|i| u64::from(i) << 40 | u64::from(i));
+        check_item_type(|i| [i; 8]);
+        check_item_type(|i| u128::from(i) << 100 | u128::from(i));
+        check_item_type(|i| [i; 16]);
+        check_item_type(|i| [u64::from(i); 4]);
+        check_item_type(|i| [i; 32]);
+    }
+
+    #[test]
+    fn test_zero_sized_fill() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Marker;
+
+        // Would take hours if the items were written one by one
+        let mut buf = vec![Marker; 3];
+        rle_decode(&mut buf, 1, usize::MAX - 3);
+        assert_eq!(buf.len(), usize::MAX);
+        assert_eq!(buf[usize::MAX - 1], Marker);
+
+        for lookbehind in 1..=3 {
+            for fill in 0..20 {
+                let mut buf = vec![(); 3];
+                rle_decode(&mut buf, lookbehind, fill);
+                assert_eq!(buf.len(), 3 + fill);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single() {
+        for fill in 0..40 {
+            let mut buf = vec![(1u32, 2u64), (3, 4)];
+            rle_decode(&mut buf, 1, fill);
+            assert_eq!(buf.len(), 2 + fill);
+            assert!(buf[1..].iter().all(|&x| x == (3, 4)));
+        }
+        let mut buf = vec![1u8, 2, 3];
+        rle_decode(&mut buf, 1, 1000);
+        assert_eq!(buf.len(), 1003);
+        assert!(buf[2..].iter().all(|&x| x == 3));
+    }
+
+    #[test]
+    fn test_counted() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        assert_eq!(rle_decode_counted(&mut buf, 3, 10), 10);
+        assert_eq!(buf.len(), 15);
+        assert_eq!(rle_decode_counted(&mut buf, 1, 0), 0);
+        assert_eq!(buf.len(), 15);
+    }
+
+    #[test]
+    fn test_into_basic() {
+        let source = [1, 2, 3, 4, 5];
+        let mut out = vec![9];
+        rle_decode_into(&source, 3, 10, &mut out);
+        assert_eq!(out, &[9, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+        assert_eq!(source, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_into_shorter_than_fragment() {
+        let mut out = Vec::new();
+        rle_decode_into(&[1, 2, 3, 4, 5], 4, 2, &mut out);
+        assert_eq!(out, &[2, 3]);
+    }
+
+    #[test]
+    fn test_into_matches_in_place() {
+        let source = [7, 1, 2, 3];
+        for fill in 0..20 {
+            let mut out = vec![0, 0];
+            rle_decode_into(&source, 3, fill, &mut out);
+
+            let mut in_place = source.to_vec();
+            rle_decode(&mut in_place, 3, fill);
+            assert_eq!(out[2..], in_place[source.len()..]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_into_overflow_fragment() {
+        rle_decode_into(&[1, 2, 3], 4, 10, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_with_pattern() {
+        let pattern = [5u16, 6, 7, 8, 9];
+        for pattern_len in 1..=pattern.len() {
+            let pattern = &pattern[..pattern_len];
+            for fill in 0..25 {
+                let mut out = vec![1, 2];
+                rle_decode_with_pattern(pattern, fill, &mut out);
+                assert_eq!(out.len(), 2 + fill);
+                assert_eq!(out[..2], [1, 2]);
+                for i in 0..fill {
+                    assert_eq!(out[2 + i], pattern[i % pattern_len]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_with_pattern_empty() {
+        rle_decode_with_pattern::<u8>(&[], 1, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_scatter_across_seam() {
+        let pattern = [1u8, 2, 3, 4, 5];
+        for first_cap in 0..12 {
+            for fill in 0..25 {
+                let mut first = vec![0];
+                let mut second = vec![9];
+                rle_decode_scatter(&pattern, fill, &mut first, first_cap, &mut second);
+                assert_eq!(first.len(), cmp::max(1, cmp::min(first_cap, 1 + fill)));
+                assert_eq!((first[0], second[0]), (0, 9));
+
+                let filled: Vec<u8> = first[1..].iter().chain(&second[1..]).cloned().collect();
+                assert_eq!(filled.len(), fill);
+                for (i, &item) in filled.iter().enumerate() {
+                    assert_eq!(item, pattern[i % pattern.len()]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scatter_seam_mid_pattern() {
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        rle_decode_scatter(&[1, 2, 3, 4], 11, &mut first, 6, &mut second);
+        assert_eq!(first, &[1, 2, 3, 4, 1, 2]);
+        assert_eq!(second, &[3, 4, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_scatter_empty_pattern() {
+        rle_decode_scatter::<u8>(&[], 1, &mut Vec::new(), 1, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_overwrite_mid_buffer() {
+        let mut frame = [0u16; 16];
+        for (i, item) in frame[12..].iter_mut().enumerate() {
+            *item = i as u16 + 1;
+        }
+        assert_eq!(rle_decode_overwrite(&mut frame, 12, 3, 4, 7), Ok(()));
+        assert_eq!(frame[..4], [0, 0, 0, 0]);
+        assert_eq!(frame[4..11], [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(frame[11..], [0, 1, 2, 3, 4]);
+
+        // The fill may end right where the pattern starts
+        assert_eq!(rle_decode_overwrite(&mut frame, 12, 4, 0, 12), Ok(()));
+        assert_eq!(frame[..12], [1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_overwrite_errors() {
+        let mut frame = [1u8, 2, 3, 4, 5, 6];
+        assert_eq!(
+            rle_decode_overwrite(&mut frame, 0, 2, 3, 4),
+            Err(RleError::RangeOutOfBounds { start: 3, end: 7, len: 6 }),
+        );
+        assert_eq!(
+            rle_decode_overwrite(&mut frame, 5, 2, 0, 1),
+            Err(RleError::RangeOutOfBounds { start: 5, end: 7, len: 6 }),
+        );
+        assert_eq!(
+            rle_decode_overwrite(&mut frame, 1, usize::MAX, 0, 1),
+            Err(RleError::RangeOutOfBounds { start: 1, end: usize::MAX, len: 6 }),
+        );
+        assert_eq!(
+            rle_decode_overwrite(&mut frame, 0, 3, 2, 2),
+            Err(RleError::Overlap { pattern_start: 0, pattern_end: 3, fill_start: 2, fill_end: 4 }),
+        );
+        assert_eq!(rle_decode_overwrite(&mut frame, 2, 0, 0, 1), Err(RleError::ZeroLookbehind));
+        assert_eq!(frame, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_empty_pattern_fallible() {
+        for &fill in &[0, 1, 5] {
+            let mut buf = vec![1u8, 2, 3, 4, 5, 6];
+            assert_eq!(try_rle_decode(&mut buf, 0, fill), Err(RleError::ZeroLookbehind));
+            assert_eq!(rle_decode_slice_to(&mut buf, 3..3, 0, fill), Err(RleError::ZeroLookbehind));
+            // Reported before the bounds of the pattern
+            assert_eq!(rle_decode_slice_to(&mut buf, 9..9, 0, fill), Err(RleError::ZeroLookbehind));
+            let result = rle_decode_overwrite(&mut buf, 3, 0, 0, fill);
+            assert_eq!(result, Err(RleError::ZeroLookbehind));
+            assert_eq!(rle_decode_array::<u8, 5>(&[], fill), Err(RleError::ZeroLookbehind));
+            let mut region = [MaybeUninit::new(1u8); 8];
+            assert_eq!(
+                unsafe { rle_decode_in_place(&mut region, 2, 0, fill) },
+                Err(RleError::ZeroLookbehind),
+            );
+            assert_eq!(buf, &[1, 2, 3, 4, 5, 6]);
+        }
+    }
+
+    #[test]
+    fn test_empty_pattern_panics() {
+        use std::panic;
+
+        fn assert_zero_lookbehind(decode: impl FnOnce()) {
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(decode));
+            let message = result.unwrap_err().downcast::<String>().unwrap();
+            assert_eq!(*message, "attempt to repeat fragment of size 0");
+        }
+
+        let empty: &[u8] = &[];
+        for &fill in &[0, 1, 5] {
+            let mut buf = vec![1u8, 2, 3];
+            assert_zero_lookbehind(|| rle_decode(&mut buf, 0, fill));
+            assert_zero_lookbehind(|| rle_decode_at(&mut buf, 1, 0, fill));
+            assert_zero_lookbehind(|| rle_decode_into(&[1, 2], 0, fill, &mut buf));
+            assert_zero_lookbehind(|| rle_decode_with_pattern(empty, fill, &mut buf));
+            assert_zero_lookbehind(|| rle_fill!(&mut buf, empty, fill));
+            assert_zero_lookbehind(|| {
+                rle_decode_scatter(empty, fill, &mut Vec::new(), 2, &mut buf);
+            });
+            assert_zero_lookbehind(|| rle_decode_reuse(&mut Vec::new(), empty, fill));
+            assert_zero_lookbehind(|| { rle_decode_slice(&mut buf, 1, 0, fill); });
+            assert_zero_lookbehind(|| {
+                rle_decode_uninit(empty, &mut [MaybeUninit::uninit(); 5]);
+            });
+            assert_zero_lookbehind(|| { rle_decode_iter(empty, fill); });
+            assert_zero_lookbehind(|| rle_decode_chunks(empty, fill, |_| {}));
+            assert_eq!(buf, &[1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn test_array() {
+        assert_eq!(rle_decode_array(&[7u32], 0), Ok([7]));
+        assert_eq!(rle_decode_array(&[1u32, 2, 3], 5), Ok([1, 2, 3, 1, 2, 3, 1, 2]));
+
+        let mut expected = vec![1u64, 2, 3];
+        rle_decode(&mut expected, 3, 61);
+        assert_eq!(rle_decode_array::<u64, 64>(&[1, 2, 3], 61).unwrap()[..], expected[..]);
+    }
+
+    #[test]
+    fn test_array_errors() {
+        assert_eq!(
+            rle_decode_array::<u8, 4>(&[1, 2], 1),
+            Err(RleError::LengthMismatch { len: 3, expected: 4 }),
+        );
+        assert_eq!(
+            rle_decode_array::<u8, 4>(&[1, 2], 3),
+            Err(RleError::LengthMismatch { len: 5, expected: 4 }),
+        );
+        assert_eq!(rle_decode_array::<u8, 0>(&[], 0), Err(RleError::ZeroLookbehind));
+        assert_eq!(rle_decode_array::<u8, 4>(&[1], usize::MAX), Err(RleError::CapacityOverflow));
+    }
+
+    #[test]
+    fn test_const() {
+        const TABLE: [u16; 64] = rle_decode_const(&[1, 2, 3], 61);
+        assert_eq!(Ok(TABLE), rle_decode_array(&[1, 2, 3], 61));
+        const SINGLE: [u8; 1] = rle_decode_const(&[7], 0);
+        assert_eq!(SINGLE, [7]);
+
+        // Also callable at run time
+        let fill = std::hint::black_box(3);
+        assert_eq!(rle_decode_const::<u8, 5>(&[4, 5], fill), [4, 5, 4, 5, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "decoded length does not match output length")]
+    fn test_const_length_mismatch() {
+        rle_decode_const::<u8, 4>(&[1, 2], usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_const_empty_pattern() {
+        rle_decode_const::<u8, 0>(&[], 0);
+    }
+
+    #[test]
+    fn test_reuse() {
+        let mut scratch = Vec::new();
+        rle_decode_reuse(&mut scratch, &[1u32, 2, 3], 997);
+        let capacity = scratch.capacity();
+        let ptr = scratch.as_ptr();
+        for frame in 0..20u32 {
+            let pattern = [frame, frame + 1];
+            let fill = 998 - (frame as usize % 5) * 100;
+            rle_decode_reuse(&mut scratch, &pattern, fill);
+
+            let mut expected = pattern.to_vec();
+            rle_decode(&mut expected, 2, fill);
+            assert_eq!(scratch, expected);
+            assert_eq!(scratch.capacity(), capacity);
+            assert_eq!(scratch.as_ptr(), ptr);
+        }
+    }
+
+    #[test]
+    fn test_reuse_invalid_keeps_scratch() {
+        use std::panic;
+
+        let mut scratch = vec![1u8, 2];
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            rle_decode_reuse(&mut scratch, &[], 1);
+        }));
+        assert!(result.is_err());
+        assert_eq!(scratch, &[1, 2]);
+    }
+
+    #[test]
+    fn test_slice_to_layouts() {
+        // The fill before the pattern, right after it, and apart from it
+        for &(pattern_start, dest_start) in &[(20, 0), (0, 3), (5, 17), (30, 8)] {
+            for fill in 0..=12 {
+                let mut buf = [0u16; 40];
+                for (i, item) in buf.iter_mut().enumerate() {
+                    *item = i as u16 + 100;
+                }
+                let pattern: Vec<u16> = buf[pattern_start..(pattern_start + 3)].to_vec();
+                let result = rle_decode_slice_to(
+                    &mut buf,
+                    pattern_start..(pattern_start + 3),
+                    dest_start,
+                    fill,
+                );
+                assert_eq!(result, Ok(fill));
+                let mut expected = Vec::new();
+                rle_decode_with_pattern(&pattern, fill, &mut expected);
+                assert_eq!(buf[dest_start..(dest_start + fill)], expected[..]);
+                assert_eq!(buf[pattern_start..(pattern_start + 3)], pattern[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_to_errors() {
+        let mut buf = [1, 2, 3, 4, 5, 6, 7, 8];
+        let overlap = |fill_start, fill_end| RleError::Overlap {
+            pattern_start: 2,
+            pattern_end: 5,
+            fill_start,
+            fill_end,
+        };
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 4, 2), Err(overlap(4, 6)));
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 0, 3), Err(overlap(0, 3)));
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 3, 1), Err(overlap(3, 4)));
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 1, 6), Err(overlap(1, 7)));
+        assert_eq!(
+            rle_decode_slice_to(&mut buf, 2..2, 5, 1),
+            Err(RleError::ZeroLookbehind),
+        );
+        assert_eq!(
+            rle_decode_slice_to(&mut buf, 6..9, 0, 1),
+            Err(RleError::RangeOutOfBounds { start: 6, end: 9, len: 8 }),
+        );
+        assert_eq!(
+            rle_decode_slice_to(&mut buf, 2..5, 6, 3),
+            Err(RleError::RangeOutOfBounds { start: 6, end: 9, len: 8 }),
+        );
+        assert_eq!(
+            rle_decode_slice_to(&mut buf, 2..5, 6, usize::MAX),
+            Err(RleError::RangeOutOfBounds { start: 6, end: usize::MAX, len: 8 }),
+        );
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Adjacent on both sides, and empty fills anywhere
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 0, 2), Ok(2));
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 5, 3), Ok(3));
+        assert_eq!(rle_decode_slice_to(&mut buf, 2..5, 3, 0), Ok(0));
+        assert_eq!(buf, [3, 4, 3, 4, 5, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_slice_basic() {
+        let mut buf = [0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 9];
+        assert_eq!(rle_decode_slice(&mut buf, 1, 3, 7), 7);
+        assert_eq!(buf, [0, 1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 9]);
+    }
+
+    #[test]
+    fn test_slice_matches_vec() {
+        for lookbehind in 1..6 {
+            for fill in 0..30 {
+                let mut buf = [0u8; 64];
+                buf[..6].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+                rle_decode_slice(&mut buf, 6 - lookbehind, lookbehind, fill);
+
+                let mut expected = vec![1, 2, 3, 4, 5, 6];
+                rle_decode(&mut expected, lookbehind, fill);
+                assert_eq!(buf[..expected.len()], expected[..]);
+                assert!(buf[expected.len()..].iter().all(|&x| x == 0));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit in a slice of length 6")]
+    fn test_slice_too_small() {
+        let mut buf = [1, 2, 3, 0, 0, 0];
+        rle_decode_slice(&mut buf, 0, 3, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit in a slice of length 3")]
+    fn test_slice_length_overflow() {
+        let mut buf = [1, 2, 3];
+        rle_decode_slice(&mut buf, 1, 2, usize::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_zero_fragment() {
+        let mut buf = [1, 2, 3];
+        rle_decode_slice(&mut buf, 1, 0, 1);
+    }
+
+    #[test]
+    fn test_uninit() {
+        let mut out: Vec<u32> = Vec::with_capacity(10);
+        let written = rle_decode_uninit(&[1, 2, 3], &mut out.spare_capacity_mut()[..10]);
+        assert_eq!(written, 10);
+        unsafe {
+            out.set_len(written);
+        }
+        assert_eq!(out, &[1, 2, 3, 1, 2, 3, 1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_with_dict_matches_concatenation() {
+        let dict = [1, 2, 3, 4];
+        for buffer_len in 0..4 {
+            for lookbehind in 1..=(dict.len() + buffer_len) {
+                for fill_length in 0..20 {
+                    let mut buffer: Vec<_> = (10..(10 + buffer_len)).collect();
+                    let mut expected = dict.to_vec();
+                    expected.extend_from_slice(&buffer);
+                    rle_decode_with_dict(&dict, &mut buffer, lookbehind, fill_length);
+                    rle_decode(&mut expected, lookbehind, fill_length);
+                    assert_eq!(buffer[..], expected[dict.len()..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_dict_straddles_seam() {
+        let mut buffer = vec![b'c', b'd'];
+        rle_decode_with_dict(b"xyab", &mut buffer, 4, 10);
+        assert_eq!(buffer, b"cdabcdabcdab");
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment larger than buffer size (5 > 4)")]
+    fn test_with_dict_too_far() {
+        let mut buffer = vec![1];
+        rle_decode_with_dict(&[1, 2, 3], &mut buffer, 5, 1);
+    }
+
+    #[test]
+    fn test_chunks_match_vec() {
+        let pattern = [10u16, 20, 30, 40];
+        for lookbehind in 1..=pattern.len() {
+            for fill_length in 0..25 {
+                let mut out = pattern[..lookbehind].to_vec();
+                let mut sizes = Vec::new();
+                rle_decode_chunks(&pattern[..lookbehind], fill_length, |chunk| {
+                    sizes.push(chunk.len());
+                    out.extend_from_slice(chunk);
+                });
+
+                let mut expected = pattern[..lookbehind].to_vec();
+                rle_decode(&mut expected, lookbehind, fill_length);
+                assert_eq!(out, expected);
+                assert!(sizes[..sizes.len().saturating_sub(1)].iter().all(|&n| n == lookbehind));
+                assert!(!sizes.contains(&0));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chunks_empty_lookbehind() {
+        rle_decode_chunks(&[] as &[u8], 1, |_| {});
+    }
+
+    #[test]
+    fn test_in_place() {
+        let mut region = [MaybeUninit::<u32>::uninit(); 12];
+        for (i, item) in region[..3].iter_mut().enumerate() {
+            *item = MaybeUninit::new(i as u32 + 1);
+        }
+        // Exactly fills the region
+        let len = unsafe { rle_decode_in_place(&mut region, 3, 2, 9) };
+        assert_eq!(len, Ok(12));
+        let decoded: Vec<u32> = region.iter().map(|item| unsafe { item.assume_init() }).collect();
+        assert_eq!(decoded, &[1, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2]);
+
+        assert_eq!(
+            unsafe { rle_decode_in_place(&mut region, 12, 1, 1) },
+            Err(RleError::OutputTooLarge { len: 13, max_len: 12 }),
+        );
+        assert_eq!(
+            unsafe { rle_decode_in_place(&mut region, 4, 3, 9) },
+            Err(RleError::OutputTooLarge { len: 13, max_len: 12 }),
+        );
+        // Nothing was written by the failed calls
+        assert_eq!(unsafe { region[4].assume_init() }, 3);
+        assert_eq!(
+            unsafe { rle_decode_in_place(&mut region, 4, 5, 1) },
+            Err(RleError::LookbehindTooLarge { lookbehind: 5, buffer_len: 4 }),
+        );
+        assert_eq!(
+            unsafe { rle_decode_in_place(&mut region, 0, 1, 1) },
+            Err(RleError::EmptyBuffer),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "initialized length is beyond the end of the region")]
+    fn test_in_place_initialized_len_too_large() {
+        let mut region = [MaybeUninit::new(1u8); 4];
+        let _ = unsafe { rle_decode_in_place(&mut region, 5, 1, 0) };
+    }
+
+    #[test]
+    fn test_uninit_short_fill() {
+        let mut fill = [MaybeUninit::<u16>::uninit(); 2];
+        assert_eq!(rle_decode_uninit(&[5, 6, 7], &mut fill), 2);
+        let fill = unsafe { [fill[0].assume_init(), fill[1].assume_init()] };
+        assert_eq!(fill, [5, 6]);
+    }
+
+    #[test]
+    fn test_uninit_matches_vec() {
+        let pattern = [(1u8, 1u64), (2, 2), (3, 3), (4, 4)];
+        for lookbehind in 1..=pattern.len() {
+            for fill_length in 0..25 {
+                let mut fill = vec![MaybeUninit::uninit(); fill_length];
+                rle_decode_uninit(&pattern[..lookbehind], &mut fill);
+                let fill: Vec<_> = fill.iter().map(|x| unsafe { x.assume_init() }).collect();
+
+                let mut expected = pattern[..lookbehind].to_vec();
+                rle_decode(&mut expected, lookbehind, fill_length);
+                assert_eq!(fill[..], expected[lookbehind..]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_uninit_empty_lookbehind() {
+        let mut fill = [MaybeUninit::<u8>::uninit(); 2];
+        rle_decode_uninit(&[], &mut fill);
+    }
+
+    #[test]
+    fn test_clone_basic() {
+        let mut buf: Vec<Box<u32>> = vec![1, 2, 3, 4, 5].into_iter().map(Box::new).collect();
+        rle_decode_clone(&mut buf, 3, 10);
+        let unboxed: Vec<u32> = buf.iter().map(|b| **b).collect();
+        assert_eq!(unboxed, &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+    }
+
+    #[test]
+    fn test_clone_panic_safety() {
+        use std::{cell::Cell, panic, rc::Rc};
+
+        struct Bomb {
+            id: u32,
+            clones_left: Rc<Cell<u32>>,
+            drops: Rc<Cell<u32>>,
+        }
+
+        impl Clone for Bomb {
+            fn clone(&self) -> Self {
+                let left = self.clones_left.get();
+                if left == 0 {
+                    panic!("clone failed");
+                }
+                self.clones_left.set(left - 1);
+                Bomb { id: self.id, clones_left: self.clones_left.clone(), drops: self.drops.clone() }
+            }
+        }
+
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+            }
+        }
+
+        let clones_left = Rc::new(Cell::new(4));
+        let drops = Rc::new(Cell::new(0));
+        let mut buf: Vec<Bomb> = (0..3)
+            .map(|id| Bomb { id, clones_left: clones_left.clone(), drops: drops.clone() })
+            .collect();
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            rle_decode_clone(&mut buf, 2, 10);
+        }));
+        assert!(result.is_err());
+        let ids: Vec<u32> = buf.iter().map(|b| b.id).collect();
+        assert_eq!(ids, &[0, 1, 2, 1, 2, 1, 2]);
+
+        drop(buf);
+        assert_eq!(drops.get(), 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_clone_zero_fragment() {
+        let mut buf = vec![String::from("a")];
+        rle_decode_clone(&mut buf, 0, 10);
+    }
+
+    #[test]
+    fn test_append_from_within() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        append_from_within(&mut buf, 1..3);
+        assert_eq!(buf, &[1, 2, 3, 4, 5, 2, 3]);
+        append_from_within(&mut buf, 5..);
+        assert_eq!(buf, &[1, 2, 3, 4, 5, 2, 3, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "src is out of bounds")]
+    fn test_append_from_within_out_of_bounds() {
+        let mut buf = vec![1, 2, 3];
+        append_from_within(&mut buf, 2..4);
+    }
+
+    #[test]
+    fn test_append_from_within_out_of_bounds_no_alloc() {
+        use std::panic;
+
+        let mut buf = vec![1u64, 2, 3];
+        buf.shrink_to_fit();
+        for range in [0..(usize::MAX / 16), 1..4] {
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                append_from_within(&mut buf, range);
+            }));
+            let message = *result.unwrap_err().downcast::<&str>().unwrap();
+            assert_eq!(message, "src is out of bounds");
+            assert_eq!(buf.capacity(), 3);
+        }
+    }
+
+    #[test]
+    fn test_try_append_from_within() {
+        let mut buf = vec![1, 2, 3];
+        try_append_from_within(&mut buf, ..).unwrap();
+        try_append_from_within(&mut buf, 6..).unwrap();
+        try_append_from_within(&mut buf, (ops::Bound::Excluded(0), ops::Bound::Included(1)))
+            .unwrap();
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 2]);
+
+        let out_of_bounds = |start, end| RleError::RangeOutOfBounds { start, end, len: 7 };
+        assert_eq!(try_append_from_within(&mut buf, 5..8), Err(out_of_bounds(5, 8)));
+        assert_eq!(try_append_from_within(&mut buf, ..=7), Err(out_of_bounds(0, 8)));
+        assert_eq!(try_append_from_within(&mut buf, 8..), Err(out_of_bounds(8, 7)));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 3..2;
+        assert_eq!(try_append_from_within(&mut buf, reversed), Err(out_of_bounds(3, 2)));
+        assert_eq!(
+            try_append_from_within(&mut buf, 1..=usize::MAX),
+            Err(out_of_bounds(1, usize::MAX)),
+        );
+        let excluded_max = (ops::Bound::Excluded(usize::MAX), ops::Bound::Unbounded);
+        assert_eq!(
+            try_append_from_within(&mut buf, excluded_max),
+            Err(out_of_bounds(usize::MAX, 7)),
+        );
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 2]);
+
+        let mut zst = vec![(); usize::MAX];
+        assert_eq!(try_append_from_within(&mut zst, 3..4), Err(RleError::CapacityOverflow));
+    }
+
+    #[test]
+    fn test_try_append_from_within_alloc_failure() {
+        /// A buffer whose allocations always fail
+        struct Full(Vec<u8>);
+
+        unsafe impl RleBuffer<u8> for Full {
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            fn reserve(&mut self, _: usize) {
+                panic!("reserve() called instead of try_reserve()");
+            }
+
+            fn try_reserve(&mut self, _: usize) -> Result<(), RleError> {
+                Err(RleError::Alloc)
+            }
+
+            fn as_slice(&self) -> &[u8] {
+                &self.0
+            }
+
+            unsafe fn extend_from_within_unchecked(&mut self, _: usize, _: usize) {
+                unreachable!()
+            }
+        }
+
+        let mut buf = Full(vec![1, 2, 3]);
+        assert_eq!(try_append_from_within(&mut buf, 1..), Err(RleError::Alloc));
+        assert_eq!(buf.0, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_append_from_within_bounds() {
+        let mut buf = vec![1, 2, 3];
+        append_from_within(&mut buf, ..);
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3]);
+        append_from_within(&mut buf, ..=1);
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2]);
+        append_from_within(&mut buf, 8..);
+        append_from_within(&mut buf, 3..3);
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2]);
+        append_from_within(&mut buf, (ops::Bound::Excluded(6), ops::Bound::Unbounded));
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "src is out of bounds")]
+    fn test_append_from_within_inclusive_out_of_bounds() {
+        let mut buf = vec![1, 2, 3];
+        append_from_within(&mut buf, ..=3);
+    }
+
+    #[test]
+    #[should_panic(expected = "src end is before src start")]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_append_from_within_reversed() {
+        let mut buf = vec![1, 2, 3];
+        append_from_within(&mut buf, 2..1);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempted to index vec up to maximum usize")]
+    fn test_append_from_within_max_usize() {
+        let mut buf = vec![1, 2, 3];
+        append_from_within(&mut buf, 1..=usize::MAX);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "assertion failed")]
+    fn test_append_from_within_unchecked_overlap() {
+        let mut buf = vec![1, 2, 3];
+        buf.reserve(3);
+        // Caught by the debug assertion before anything is copied
+        unsafe { append_from_within_unchecked(&mut buf, 2, 3) };
+    }
+
+    #[test]
+    fn test_lenient() {
+        let mut buf = vec![1, 2, 3];
+        rle_decode_lenient(&mut buf, 0, 10);
+        rle_decode_lenient(&mut buf, 0, usize::MAX);
+        assert_eq!(buf, &[1, 2, 3]);
+        rle_decode_lenient(&mut buf, 2, 3);
+        assert_eq!(buf, &[1, 2, 3, 2, 3, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment larger than buffer size")]
+    fn test_lenient_lookbehind_too_large() {
+        rle_decode_lenient(&mut vec![1, 2, 3], 4, 1);
+    }
+
+    #[test]
+    fn test_modular() {
+        for initial_len in 1..5 {
+            for lookbehind in 1..12 {
+                for fill in 0..30 {
+                    let mut decoded: Vec<u8> = (1..=initial_len as u8).collect();
+                    rle_decode_modular(&mut decoded, lookbehind, fill);
+
+                    // An item by item producer
+                    let mut expected: Vec<u8> = (1..=initial_len as u8).collect();
+                    for i in initial_len..(initial_len + fill) {
+                        let distance = if i < lookbehind { initial_len } else { lookbehind };
+                        expected.push(expected[i - distance]);
+                    }
+                    assert_eq!(decoded, expected, "lookbehind {}, fill {}", lookbehind, fill);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of empty buffer")]
+    fn test_modular_empty_buffer() {
+        rle_decode_modular(&mut Vec::<u8>::new(), 4, 1);
+    }
+
+    #[test]
+    fn test_str() {
+        let mut text = String::from("x🦀é");
+        rle_decode_str(&mut text, 6, 6 * 3 + 4).unwrap();
+        assert_eq!(text, "x🦀é🦀é🦀é🦀é🦀");
+        rle_decode_str(&mut text, 4, 8).unwrap();
+        assert!(text.ends_with("🦀🦀🦀"));
+    }
+
+    #[test]
+    fn test_str_errors() {
+        let mut text = String::from("x🦀é");
+        // Starts inside the crab
+        assert_eq!(rle_decode_str(&mut text, 4, 4), Err(RleError::MalformedInput));
+        // Ends inside the crab
+        assert_eq!(rle_decode_str(&mut text, 6, 7), Err(RleError::MalformedInput));
+        // Ends inside the é
+        assert_eq!(rle_decode_str(&mut text, 6, 5), Err(RleError::MalformedInput));
+        assert_eq!(rle_decode_str(&mut text, 0, 1), Err(RleError::ZeroLookbehind));
+        assert_eq!(
+            rle_decode_str(&mut text, 8, 1),
+            Err(RleError::LookbehindTooLarge { lookbehind: 8, buffer_len: 7 }),
+        );
+        assert_eq!(text, "x🦀é");
+    }
+
+    #[test]
+    fn test_rle_fill_detached_pattern() {
+        for fill in 0..20 {
+            let mut buf = [0u16; 32];
+            buf[..3].copy_from_slice(&[1, 2, 3]);
+            unsafe { rle_fill(buf.as_mut_ptr(), 0, 3, 10, fill) };
+            assert_eq!(buf[..10], [1, 2, 3, 0, 0, 0, 0, 0, 0, 0]);
+            let expected: Vec<u16> = [1, 2, 3].iter().copied().cycle().take(fill).collect();
+            assert_eq!(buf[10..(10 + fill)], expected[..]);
+            assert!(buf[(10 + fill)..].iter().all(|&x| x == 0));
+        }
+
+        // The pattern may also come after the destination
+        let mut buf = [0u8, 0, 0, 0, 0, 7, 8];
+        unsafe { rle_fill(buf.as_mut_ptr(), 5, 2, 0, 5) };
+        assert_eq!(buf, [7, 8, 7, 8, 7, 7, 8]);
+    }
+
+    /// Copies one item at a time, the textbook LZ77 way
+    fn naive_copy_back(buffer: &mut Vec<u8>, distance: usize, length: usize) {
+        for _ in 0..length {
+            buffer.push(buffer[buffer.len() - distance]);
+        }
+    }
+
+    #[test]
+    fn test_small_fill_threshold() {
+        for lookbehind in 1..=(SMALL_FILL + 2) {
+            for fill in 0..=(2 * SMALL_FILL + 1) {
+                let mut decoded: Vec<u8> = (1..=20).collect();
+                let mut expected = decoded.clone();
+                rle_decode(&mut decoded, lookbehind, fill);
+                naive_copy_back(&mut expected, lookbehind, fill);
+                assert_eq!(decoded, expected, "lookbehind {}, fill {}", lookbehind, fill);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lz_copy_back() {
+        let mut buf = vec![5, 6, 7];
+        lz_copy_back(&mut buf, 1, 10);
+        assert_eq!(buf, &[5, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7]);
+
+        let mut buf = vec![5, 6, 7];
+        lz_copy_back(&mut buf, 3, 7);
+        assert_eq!(buf, &[5, 6, 7, 5, 6, 7, 5, 6, 7, 5]);
+
+        for distance in 1..=8 {
+            for length in 0..30 {
+                let mut fast: Vec<u8> = (10..18).collect();
+                let mut naive = fast.clone();
+                lz_copy_back(&mut fast, distance, length);
+                naive_copy_back(&mut naive, distance, length);
+                assert_eq!(fast, naive);
+            }
+        }
+    }
+
+    #[test]
+    fn test_front_matches_reversed() {
+        for lookbehind in 1..8 {
+            for fill in 0..30 {
+                // Pushing to the front of an empty deque starts at the end of its storage, so
+                // the fragment wraps around
+                let mut front = VecDeque::new();
+                (1..=5).rev().for_each(|i| front.push_front(i));
+                (6..=10).for_each(|i| front.push_back(i));
+                rle_decode_front(&mut front, lookbehind, fill);
+
+                let mut reversed: Vec<u8> = (1..=10).rev().collect();
+                rle_decode(&mut reversed, lookbehind, fill);
+                reversed.reverse();
+                assert!(front == reversed, "lookbehind {}, fill {}", lookbehind, fill);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment larger than buffer size")]
+    fn test_front_lookbehind_too_large() {
+        rle_decode_front(&mut VecDeque::from(vec![1, 2, 3]), 4, 1);
+    }
+
+    #[test]
+    fn test_hash_matches_decoded() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn check<T: Copy + Hash>(lookbehind: &[T], fill_length: usize) {
+            let mut streamed = DefaultHasher::new();
+            rle_decode_hash(lookbehind, fill_length, &mut streamed);
+            let mut decoded = lookbehind.to_vec();
+            rle_decode(&mut decoded, lookbehind.len(), fill_length);
+            let mut whole = DefaultHasher::new();
+            Hash::hash_slice(&decoded[lookbehind.len()..], &mut whole);
+            assert_eq!(streamed.finish(), whole.finish());
+        }
+
+        for fill_length in 0..50 {
+            check(b"abcde", fill_length);
+            check(&[1u32, 2, 3], fill_length);
+            check(&[(1u8, 'x'), (2, 'y')], fill_length);
+        }
+        check(&[7u64], 10_000);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut buf = vec![9u8, 1, 2, 3];
+        assert_eq!(rle_decode_get(&mut buf, 3, 8), &[1, 2, 3, 1, 2, 3, 1, 2]);
+        assert_eq!(rle_decode_get(&mut buf, 1, 3), &[2, 2, 2]);
+        assert!(rle_decode_get(&mut buf, 4, 0).is_empty());
+        assert_eq!(buf.len(), 15);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut buf = vec![1, 2, 3];
+        rle_decode_repeat(&mut buf, 3, 2);
+        rle_decode_repeat(&mut buf, 1, 0);
+        rle_decode_repeat(&mut buf, 2, 1);
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2, 3, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn test_repeat_overflow() {
+        rle_decode_repeat(&mut vec![1, 2, 3], 3, usize::MAX / 2);
+    }
+
+    #[test]
+    fn test_checked_fill() {
+        let mut buf = vec![1, 2, 3];
+        rle_decode_checked_fill(&mut buf, 3, 2, 3).unwrap();
+        rle_decode_checked_fill(&mut buf, 2, 0, usize::MAX).unwrap();
+        rle_decode_checked_fill(&mut buf, 2, 1, 3).unwrap();
+        assert_eq!(buf, &[1, 2, 3, 1, 2, 3, 1, 2, 3, 2, 3, 2]);
+
+        // Both overflow whatever the pointer width
+        let half = 1 << (usize::BITS / 2);
+        for &(repeat_count, stride) in &[(half, half), (usize::MAX / 3 + 1, 3), (2, usize::MAX)] {
+            assert_eq!(
+                rle_decode_checked_fill(&mut buf, 1, repeat_count, stride),
+                Err(RleError::CapacityOverflow),
+            );
+        }
+        assert_eq!(buf.len(), 12);
+    }
+
+    /// Fails to link if the compiler cannot prove that `rle_decode_nopanic()` never panics
+    #[cfg_attr(
+        not(any(debug_assertions, feature = "simd", feature = "prefetch")),
+        no_panic::no_panic
+    )]
+    fn decode_nopanic(buffer: &mut Vec<u32>, lookbehind_length: usize, fill_length: usize) {
+        unsafe { rle_decode_nopanic(buffer, lookbehind_length, fill_length) }
+    }
+
+    #[test]
+    fn test_nopanic() {
+        for lookbehind in 1..10 {
+            for fill in 0..40 {
+                let mut decoded: Vec<u32> = (0..10).collect();
+                let mut expected = decoded.clone();
+                decoded.reserve(fill);
+                decode_nopanic(&mut decoded, lookbehind, fill);
+                rle_decode(&mut expected, lookbehind, fill);
+                assert_eq!(decoded, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_doubling_remainder() {
+        for lookbehind in 1..40 {
+            for fill in 0..300 {
+                let mut bytes: Vec<u8> = (0..lookbehind as u8).collect();
+                let mut generic = bytes.clone();
+                let mut naive = bytes.clone();
+                bytes.reserve(fill);
+                generic.reserve(fill);
+                unsafe {
+                    repeat_bytes(bytes.as_mut_ptr().add(lookbehind).cast(), lookbehind, fill);
+                    bytes.set_len(lookbehind + fill);
+                    repeat_by_doubling(&mut generic, lookbehind, fill);
+                }
+                for _ in 0..fill {
+                    naive.push(naive[naive.len() - lookbehind]);
+                }
+                assert_eq!(bytes, naive);
+                assert_eq!(generic, naive);
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_empty_buffer() {
+        let mut buf: Vec<u8> = Vec::new();
+        assert_eq!(try_rle_decode(&mut buf, 1, 1), Err(RleError::EmptyBuffer));
+        assert_eq!(try_rle_decode(&mut buf, 0, 1), Err(RleError::ZeroLookbehind));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of empty buffer")]
+    fn test_empty_buffer() {
+        rle_decode(&mut Vec::<u8>::new(), 3, 1);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut buf = vec![1, 2, 3, 7, 8];
+        assert_eq!(rle_decode_bounded(&mut buf, 3, 2, 3), Ok(()));
+        assert_eq!(rle_decode_bounded(&mut buf, 3, 5, 2), Ok(()));
+        assert_eq!(buf, &[1, 2, 3, 7, 8, 7, 8, 7, 7, 8]);
+        assert_eq!(rle_decode_bounded(&mut buf, 0, 10, 1), Ok(()));
+        assert_eq!(buf.len(), 11);
+    }
+
+    #[test]
+    fn test_bounded_errors() {
+        let mut buf = vec![1, 2, 3, 7, 8];
+        assert_eq!(
+            rle_decode_bounded(&mut buf, 3, 3, 1),
+            Err(RleError::LookbehindTooLarge { lookbehind: 3, buffer_len: 2 }),
+        );
+        assert_eq!(rle_decode_bounded(&mut buf, 5, 1, 1), Err(RleError::EmptyBuffer));
+        assert_eq!(rle_decode_bounded(&mut buf, 5, 0, 1), Err(RleError::ZeroLookbehind));
+        assert_eq!(
+            rle_decode_bounded(&mut buf, 3, 1, usize::MAX),
+            Err(RleError::CapacityOverflow),
+        );
+        assert_eq!(buf, &[1, 2, 3, 7, 8]);
+    }
+
+    #[test]
+    #[should_panic(expected = "stream boundary is beyond the end of the buffer")]
+    fn test_bounded_boundary_out_of_bounds() {
+        let _ = rle_decode_bounded(&mut vec![1, 2, 3], 4, 1, 1);
+    }
+
+    #[test]
+    fn test_reserve_exact() {
+        let mut exact = vec![1u32, 2, 3];
+        exact.shrink_to_fit();
+        rle_decode_reserve_exact(&mut exact, 2, 1000);
+        let mut amortized = vec![1u32, 2, 3];
+        rle_decode(&mut amortized, 2, 1000);
+        assert_eq!(exact, amortized);
+        assert_eq!(exact.capacity(), exact.len());
+    }
+
+    #[test]
+    fn test_try_basic() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        assert_eq!(try_rle_decode(&mut buf, 3, 10), Ok(()));
+        assert_eq!(buf, &[1, 2, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3, 4, 5, 3]);
+    }
+
+    #[test]
+    fn test_try_errors() {
+        let mut buf = vec![1, 2, 3, 4, 5];
+        assert_eq!(try_rle_decode(&mut buf, 0, 10), Err(RleError::ZeroLookbehind));
+        assert_eq!(
+            try_rle_decode(&mut buf, 10, 10),
+            Err(RleError::LookbehindTooLarge { lookbehind: 10, buffer_len: 5 }),
+        );
+        assert_eq!(
+            try_rle_decode(&mut buf, 4, usize::MAX),
+            Err(RleError::CapacityOverflow),
+        );
+        assert_eq!(buf, &[1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_try_errors_leave_buffer_untouched() {
+        let cases = [
+            (0, 1, RleError::ZeroLookbehind),
+            (4, 1, RleError::LookbehindTooLarge { lookbehind: 4, buffer_len: 3 }),
+            (1, usize::MAX - 2, RleError::CapacityOverflow),
+            (1, usize::MAX / 8, RleError::CapacityOverflow),
+            (1, usize::MAX / 32, RleError::Alloc),
+        ];
+        for &(lookbehind, fill, err) in &cases {
+            let mut buf = Vec::with_capacity(5);
+            buf.extend_from_slice(&[1u64, 2, 3]);
+            let capacity = buf.capacity();
+            assert_eq!(rle_decode_exact(&mut buf, lookbehind, fill), Err(err));
+            assert_eq!(try_rle_decode(&mut buf, lookbehind, fill), Err(err));
+            assert_eq!(buf, &[1, 2, 3]);
+            assert_eq!(buf.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn test_try_alloc_failure() {
+        // More bytes than any allocator can provide, but within `isize::MAX`
+        let mut buf = vec![1u64, 2];
+        assert_eq!(try_rle_decode(&mut buf, 2, usize::MAX / 32), Err(RleError::Alloc));
+        assert_eq!(buf, &[1, 2]);
+        assert_eq!(buf.capacity(), 2);
+    }
+
+    #[test]
+    fn test_try_fill_exceeds_isize_bytes() {
+        // The item count fits in `usize`, but not the byte count in `isize`
+        let mut buf = vec![[1u8; 64], [2; 64]];
+        let fill = isize::MAX as usize / 64 + 1;
+        assert_eq!(decoded_len(buf.len(), fill), Some(fill + 2));
+        assert_eq!(try_rle_decode(&mut buf, 2, fill), Err(RleError::CapacityOverflow));
+        assert_eq!(try_rle_decode(&mut buf, 1, usize::MAX / 64), Err(RleError::CapacityOverflow));
+        assert_eq!(buf.capacity(), 2);
+
+        // Zero-sized items take no bytes
+        let mut zst = vec![(); 2];
+        assert_eq!(try_rle_decode(&mut zst, 2, usize::MAX - 2), Ok(()));
+    }
+
+    #[test]
+    fn test_unchecked_matches_safe() {
+        let mut safe = vec![1, 2, 3, 4, 5];
+        let mut fast = safe.clone();
+        fast.reserve(6 * 7 + 7);
+        for lookbehind in 1..=5 {
+            rle_decode(&mut safe, lookbehind, 7);
+            unsafe { rle_decode_unchecked(&mut fast, lookbehind, 7) };
+        }
+        assert_eq!(safe, fast);
+    }
+
+    #[test]
+    fn test_budgeted_matches_one_shot() {
+        for lookbehind in 1..=5 {
+            for &budget in &[1, 2, 3, 7, 64, 1000] {
+                let mut expected: Vec<u16> = (1..=5).collect();
+                rle_decode(&mut expected, lookbehind, 500);
+
+                let mut buf: Vec<u16> = (1..=5).collect();
+                let (mut lookbehind, mut fill) = (lookbehind, 500);
+                let mut calls = 0;
+                while let DecodeProgress::Pending { remaining_fill, current_lookbehind } =
+                    rle_decode_budgeted(&mut buf, lookbehind, fill, budget)
+                {
+                    assert_eq!(fill - remaining_fill, budget);
+                    lookbehind = current_lookbehind;
+                    fill = remaining_fill;
+                    calls += 1;
+                }
+                assert_eq!(calls, (500 - 1) / budget);
+                assert_eq!(buf, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_budgeted_zero_budget() {
+        // No progress, the same state comes back
+        let mut buf = vec![1u8, 2, 3];
+        for _ in 0..3 {
+            assert_eq!(
+                rle_decode_budgeted(&mut buf, 2, 4, 0),
+                DecodeProgress::Pending { remaining_fill: 4, current_lookbehind: 2 },
+            );
+        }
+        assert_eq!(buf, &[1, 2, 3]);
+        assert_eq!(rle_decode_budgeted(&mut buf, 2, 0, 0), DecodeProgress::Done);
+        assert_eq!(buf, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_budgeted_grows_lookbehind() {
+        let mut buf = vec![1, 2, 3];
+        assert_eq!(
+            rle_decode_budgeted(&mut buf, 2, 10, 5),
+            DecodeProgress::Pending { remaining_fill: 5, current_lookbehind: 6 },
+        );
+        assert_eq!(
+            rle_decode_budgeted(&mut buf, 6, 5, 0),
+            DecodeProgress::Pending { remaining_fill: 5, current_lookbehind: 6 },
+        );
+        assert_eq!(rle_decode_budgeted(&mut buf, 6, 5, 5), DecodeProgress::Done);
+        assert_eq!(buf, &[1, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn test_to_capacity() {
+        for lookbehind in 1..=3 {
+            let mut buf = Vec::with_capacity(37);
+            buf.extend_from_slice(&[1u32, 2, 3]);
+            let capacity = buf.capacity();
+            let ptr = buf.as_ptr();
+            assert_eq!(rle_decode_to_capacity(&mut buf, lookbehind), capacity - 3);
+            assert_eq!(buf.len(), capacity);
+            assert_eq!(buf.capacity(), capacity);
+            assert_eq!(buf.as_ptr(), ptr);
+
+            let mut expected = vec![1, 2, 3];
+            rle_decode(&mut expected, lookbehind, capacity - 3);
+            assert_eq!(buf, expected);
+
+            // A full buffer stays as it is
+            assert_eq!(rle_decode_to_capacity(&mut buf, lookbehind), 0);
+            assert_eq!(buf, expected);
+        }
+    }
+
+    #[test]
+    fn test_saturating() {
+        let mut buf = vec![1u8, 2, 3];
+        assert_eq!(rle_decode_saturating(&mut buf, 2, 5), 5);
+        assert_eq!(buf, &[1, 2, 3, 2, 3, 2, 3, 2]);
+
+        // Zero sized items can fill up the whole index range
+        let mut zst = vec![(); 5];
+        assert_eq!(rle_decode_saturating(&mut zst, 2, usize::MAX - 6), usize::MAX - 6);
+        assert_eq!(rle_decode_saturating(&mut zst, 2, 10), 1);
+        assert_eq!(zst.len(), usize::MAX);
+        assert_eq!(rle_decode_saturating(&mut zst, 2, 10), 0);
+    }
+
+    #[test]
+    fn test_max_fill() {
+        assert_eq!(max_fill::<()>(0), usize::MAX);
+        assert_eq!(max_fill::<()>(usize::MAX), 0);
+        assert_eq!(max_fill::<u8>(10), isize::MAX as usize - 10);
+        assert_eq!(max_fill::<u32>(10), isize::MAX as usize / 4 - 10);
+        assert_eq!(max_fill::<[u8; 64]>(usize::MAX), 0);
+    }
+
+    #[test]
+    fn test_fill_schedule() {
+        // The example of the README and crate docs
+        assert_eq!(fill_schedule(4, 10), [4, 4, 2]);
+        assert_eq!(fill_schedule(2, 0), []);
+        assert_eq!(fill_schedule(100, 9), [9]);
+        assert_eq!(fill_schedule(2, 100), [2, 2, 4, 8, 16, 32, 36]);
+        for lookbehind in 1..10 {
+            for fill in 0..100 {
+                assert_eq!(fill_schedule(lookbehind, fill).iter().sum::<usize>(), fill);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes_outlined() {
+        for lookbehind in 1..=4 {
+            let mut outlined = vec![1u8, 2, 3, 4];
+            let mut inlined = outlined.clone();
+            rle_decode_bytes_outlined(&mut outlined, lookbehind, 50);
+            rle_decode(&mut inlined, lookbehind, 50);
+            assert_eq!(outlined, inlined);
+        }
+    }
+
+    #[test]
+    fn test_at_middle_of_buffer() {
+        let initial: Vec<u32> = (0..12).collect();
+        for pattern_start in 0..12 {
+            for pattern_len in 1..=(12 - pattern_start) {
+                for &fill in &[0, 1, 5, 8, 9, 40] {
+                    let mut buf = initial.clone();
+                    rle_decode_at(&mut buf, pattern_start, pattern_len, fill);
+                    assert_eq!(buf[..12], initial[..]);
+                    for i in 0..fill {
+                        assert_eq!(buf[12 + i], initial[pattern_start + i % pattern_len]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_at_tail_matches_rle_decode() {
+        for lookbehind in 1..=5 {
+            let mut at = vec![1, 2, 3, 4, 5];
+            let mut tail = at.clone();
+            rle_decode_at(&mut at, 5 - lookbehind, lookbehind, 23);
+            rle_decode(&mut tail, lookbehind, 23);
+            assert_eq!(at, tail);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "source range 3..6 is out of bounds of buffer of length 5")]
+    fn test_at_out_of_bounds() {
+        rle_decode_at(&mut vec![1, 2, 3, 4, 5], 3, 3, 1);
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        const LEN: Option<usize> = decoded_len(3, 5);
+        assert_eq!(LEN, Some(8));
+        assert_eq!(decoded_len(usize::MAX, 0), Some(usize::MAX));
+        assert_eq!(decoded_len(usize::MAX, 1), None);
+    }
+
+    #[test]
+    fn test_limited() {
+        let mut buf = vec![1, 2, 3];
+        assert_eq!(rle_decode_limited(&mut buf, 2, 3, 6), Ok(()));
+        assert_eq!(buf, &[1, 2, 3, 2, 3, 2]);
+        assert_eq!(
+            rle_decode_limited(&mut buf, 2, 1, 6),
+            Err(RleError::OutputTooLarge { len: 7, max_len: 6 }),
+        );
+        // Rejected before the reserve, which would abort the test process
+        assert_eq!(
+            rle_decode_limited(&mut buf, 1, usize::MAX / 2, 1 << 20),
+            Err(RleError::OutputTooLarge { len: usize::MAX / 2 + 6, max_len: 1 << 20 }),
+        );
+        assert_eq!(rle_decode_limited(&mut buf, 0, 1, 100), Err(RleError::ZeroLookbehind));
+        assert_eq!(buf, &[1, 2, 3, 2, 3, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_try_error_composes() {
+        fn decode(buf: &mut Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+            try_rle_decode(buf, 0, 1)?;
+            Ok(())
+        }
+        let err = decode(&mut vec![1, 2, 3]).unwrap_err();
+        assert_eq!(err.to_string(), "attempt to repeat fragment of size 0");
     }
 }
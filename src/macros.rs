@@ -0,0 +1,81 @@
+/// Appends `fill_length` items to a `Vec` by repeating a pattern, like
+/// [`rle_decode_with_pattern`](crate::rle_decode_with_pattern)
+///
+/// With the pattern written out as an array literal, its length is known at compile time, so
+/// every repetition of a short fill becomes a few direct stores instead of a call to `memcpy`.
+/// Long fills, and any other pattern expression such as a slice, are passed on to
+/// `rle_decode_with_pattern`. The output is the same either way.
+///
+/// ```rust
+/// use rle_decode_helper::rle_fill;
+///
+/// let mut out = vec![0];
+/// rle_fill!(&mut out, [1, 2, 3], 7);
+/// assert_eq!(out, &[0, 1, 2, 3, 1, 2, 3, 1]);
+///
+/// let pattern = vec![4, 5];
+/// rle_fill!(&mut out, &pattern, 3);
+/// assert_eq!(out[8..], [4, 5, 4]);
+/// ```
+///
+/// # Panics
+/// * the pattern is empty
+/// * `fill_length + out.len()` would overflow
+#[macro_export]
+macro_rules! rle_fill {
+    ($out:expr, [$($item:expr),+ $(,)?], $fill_length:expr $(,)?) => {{
+        let out = $out;
+        let pattern = [$($item),+];
+        let fill_length: usize = $fill_length;
+        if fill_length > $crate::__MACRO_FILL_MAX {
+            $crate::rle_decode_with_pattern(&pattern, fill_length, out);
+        } else {
+            out.reserve(fill_length);
+            for _ in 0..(fill_length / pattern.len()) {
+                out.extend_from_slice(&pattern);
+            }
+            out.extend_from_slice(&pattern[..(fill_length % pattern.len())]);
+        }
+    }};
+    ($out:expr, $pattern:expr, $fill_length:expr $(,)?) => {
+        $crate::rle_decode_with_pattern($pattern, $fill_length, $out)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::rle_decode_with_pattern;
+
+    #[test]
+    fn test_matches_function() {
+        for fill in 0..100 {
+            let mut expected = vec![9u16];
+            rle_decode_with_pattern(&[1, 2, 3], fill, &mut expected);
+            let mut out = vec![9u16];
+            rle_fill!(&mut out, [1, 2, 3], fill);
+            assert_eq!(out, expected);
+
+            let mut expected = Vec::new();
+            rle_decode_with_pattern(&[7], fill, &mut expected);
+            let mut out = Vec::new();
+            rle_fill!(&mut out, [7u8,], fill);
+            assert_eq!(out, expected);
+
+            let pattern = [4u32, 5, 6, 7, 8];
+            let mut expected = Vec::new();
+            rle_decode_with_pattern(&pattern, fill, &mut expected);
+            let mut out = Vec::new();
+            rle_fill!(&mut out, &pattern[..], fill);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_empty_slice() {
+        let empty: &[u8] = &[];
+        rle_fill!(&mut Vec::new(), empty, 1);
+    }
+}
@@ -0,0 +1,166 @@
+use alloc::vec::Vec;
+
+use crate::{check_fill_bytes, fragment_start, rle_decode_unchecked, RleError};
+
+/// Configurable decoding, for the cases that [`rle_decode`](crate::rle_decode) does not cover
+///
+/// Collects the optional behaviors of the specialized decode functions in one place, so they
+/// can be combined. The default options decode exactly like
+/// [`try_rle_decode`](crate::try_rle_decode).
+///
+/// ```rust
+/// use rle_decode_helper::{RleError, RleOptions};
+///
+/// let options = RleOptions::new().max_output(8).lenient_zero(true);
+/// let mut buffer = vec![1, 2];
+/// options.decode(&mut buffer, 0, 100).unwrap();
+/// options.decode(&mut buffer, 2, 4).unwrap();
+/// assert_eq!(buffer, &[1, 2, 1, 2, 1, 2]);
+/// assert_eq!(
+///     options.decode(&mut buffer, 2, 4),
+///     Err(RleError::OutputTooLarge { len: 10, max_len: 8 }),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RleOptions {
+    max_output: Option<usize>,
+    exact_capacity: bool,
+    lenient_zero: bool,
+}
+
+impl RleOptions {
+    /// Creates the default options: no length limit, amortized growth, and an error for a
+    /// `lookbehind_length` of 0
+    pub const fn new() -> Self {
+        RleOptions {
+            max_output: None,
+            exact_capacity: false,
+            lenient_zero: false,
+        }
+    }
+
+    /// Refuses to grow the buffer beyond `max_len` items in total, see
+    /// [`rle_decode_limited`](crate::rle_decode_limited)
+    pub const fn max_output(mut self, max_len: usize) -> Self {
+        self.max_output = Some(max_len);
+        self
+    }
+
+    /// Grows the buffer only by what the output needs, see
+    /// [`rle_decode_reserve_exact`](crate::rle_decode_reserve_exact)
+    pub const fn exact_capacity(mut self, exact_capacity: bool) -> Self {
+        self.exact_capacity = exact_capacity;
+        self
+    }
+
+    /// Treats a `lookbehind_length` of 0 as a no-op instead of an error, see
+    /// [`rle_decode_lenient`](crate::rle_decode_lenient)
+    pub const fn lenient_zero(mut self, lenient_zero: bool) -> Self {
+        self.lenient_zero = lenient_zero;
+        self
+    }
+
+    /// Appends `fill_length` items by repeating the last `lookbehind_length` items of `buffer`,
+    /// with these options applied
+    ///
+    /// The buffer is left untouched if an error is returned.
+    ///
+    /// # Errors
+    /// * [`RleError::OutputTooLarge`] if the buffer would grow beyond the
+    ///   [`max_output`](Self::max_output) limit
+    /// * everything [`try_rle_decode`](crate::try_rle_decode) reports, except for
+    ///   [`RleError::ZeroLookbehind`] with [`lenient_zero`](Self::lenient_zero)
+    pub fn decode<T>(
+        &self,
+        buffer: &mut Vec<T>,
+        lookbehind_length: usize,
+        fill_length: usize,
+    ) -> Result<(), RleError> where T: Copy {
+        if lookbehind_length == 0 && self.lenient_zero {
+            return Ok(());
+        }
+        fragment_start(buffer.len(), lookbehind_length, fill_length)?;
+        if let Some(max_len) = self.max_output {
+            // Cannot overflow, fragment_start() checked it
+            let len = buffer.len() + fill_length;
+            if len > max_len {
+                return Err(RleError::OutputTooLarge { len, max_len });
+            }
+        }
+        check_fill_bytes::<T>(fill_length)?;
+
+        let reserved = if self.exact_capacity {
+            buffer.try_reserve_exact(fill_length)
+        } else {
+            buffer.try_reserve(fill_length)
+        };
+        reserved.map_err(|_| RleError::Alloc)?;
+
+        unsafe {
+            // The lengths were validated above, and space for all copies was reserved
+            rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rle_decode, try_rle_decode};
+
+    #[test]
+    fn test_default_matches_rle_decode() {
+        let mut configured = vec![1u16, 2, 3];
+        let mut plain = configured.clone();
+        RleOptions::default().decode(&mut configured, 2, 50).unwrap();
+        rle_decode(&mut plain, 2, 50);
+        assert_eq!(configured, plain);
+        assert_eq!(
+            RleOptions::new().decode(&mut configured, 0, 1),
+            Err(RleError::ZeroLookbehind),
+        );
+    }
+
+    #[test]
+    fn test_default_errors_match_try_rle_decode() {
+        let cases = [
+            (0, 1),
+            (4, 1),
+            (1, usize::MAX - 2),
+            (1, usize::MAX / 8),
+            (1, usize::MAX / 16),
+            (1, usize::MAX / 32),
+        ];
+        for &(lookbehind, fill) in &cases {
+            let mut configured = vec![1u64, 2, 3];
+            let mut plain = configured.clone();
+            assert_eq!(
+                RleOptions::new().decode(&mut configured, lookbehind, fill),
+                try_rle_decode(&mut plain, lookbehind, fill),
+            );
+            assert_eq!(configured, plain);
+            assert_eq!(configured.capacity(), 3);
+        }
+    }
+
+    #[test]
+    fn test_combined_options() {
+        let options = RleOptions::new().max_output(100).exact_capacity(true).lenient_zero(true);
+        let mut buffer = vec![1u8, 2, 3];
+        buffer.shrink_to_fit();
+        options.decode(&mut buffer, 0, usize::MAX).unwrap();
+        options.decode(&mut buffer, 3, 97).unwrap();
+        assert_eq!(buffer.len(), 100);
+        assert_eq!(buffer.capacity(), 100);
+        assert_eq!(
+            options.decode(&mut buffer, 1, 1),
+            Err(RleError::OutputTooLarge { len: 101, max_len: 100 }),
+        );
+        assert_eq!(
+            options.decode(&mut buffer, 101, 0),
+            Err(RleError::LookbehindTooLarge { lookbehind: 101, buffer_len: 100 }),
+        );
+        assert_eq!(buffer.len(), 100);
+    }
+}
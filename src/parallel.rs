@@ -0,0 +1,165 @@
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+
+use alloc::vec::Vec;
+use rayon::prelude::*;
+
+use crate::{
+    check_fragment, decode_fail, fragment_start, rle_decode_unchecked, rle_decode_with_pattern,
+};
+
+/// Fills shorter than this many items are decoded on the calling thread by
+/// [`rle_decode_parallel`]
+pub const PARALLEL_MIN_FILL: usize = 1 << 22;
+
+/// Approximate size of the chunk filled by every task
+const CHUNK_BYTES: usize = 1 << 20;
+
+/// Fast decoding of huge runs on multiple threads
+///
+/// Same as [`rle_decode`](crate::rle_decode), but fills of at least [`PARALLEL_MIN_FILL`]
+/// items are split into chunks that are copied in parallel on the `rayon` thread pool. Only
+/// worthwhile for single runs of many megabytes, below that the overhead of the threads
+/// outweighs the copying.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+pub fn rle_decode_parallel<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy + Send + Sync {
+    rle_decode_parallel_with_threshold(buffer, lookbehind_length, fill_length, PARALLEL_MIN_FILL)
+}
+
+/// Same as [`rle_decode_parallel`], but runs shorter than `min_parallel_fill` items are
+/// decoded on the calling thread instead of [`PARALLEL_MIN_FILL`]
+///
+/// The best threshold depends on the machine and the item size, so measure before changing it.
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+pub fn rle_decode_parallel_with_threshold<T>(
+    buffer: &mut Vec<T>,
+    lookbehind_length: usize,
+    fill_length: usize,
+    min_parallel_fill: usize,
+) where T: Copy + Send + Sync {
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
+    }
+    buffer.reserve(fill_length);
+
+    // Every chunk starts at a whole number of fragments into the output, so each one is a
+    // prefix of the first chunk
+    let chunk_items = CHUNK_BYTES / mem::size_of::<T>().max(1);
+    let chunk_len = lookbehind_length.saturating_mul(chunk_items.div_ceil(lookbehind_length));
+    if fill_length < min_parallel_fill || fill_length <= chunk_len {
+        unsafe {
+            // The lengths were validated above, and space for all copies was reserved
+            rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+        }
+        return;
+    }
+
+    let len = buffer.len();
+    unsafe {
+        // The first chunk is the source of all others, so it has to be complete before they
+        // are started
+        rle_decode_unchecked(buffer, lookbehind_length, chunk_len);
+
+        // The template and the rest of the output are disjoint parts of the allocation, and
+        // both were reserved above
+        let base = buffer.as_mut_ptr();
+        let template: &[T] = core::slice::from_raw_parts(base.add(len), chunk_len);
+        let rest: &mut [MaybeUninit<T>] = core::slice::from_raw_parts_mut(
+            base.add(len + chunk_len).cast(),
+            fill_length - chunk_len,
+        );
+        rest.par_chunks_mut(chunk_len).for_each(|chunk| {
+            ptr::copy_nonoverlapping(template.as_ptr().cast(), chunk.as_mut_ptr(), chunk.len());
+        });
+
+        // All items up to the new length were written above
+        buffer.set_len(len + fill_length);
+    }
+}
+
+/// Appends the requested number of items to each of many buffers, all repeating `pattern`
+///
+/// Every `(buffer, fill_length)` pair is filled like
+/// [`rle_decode_with_pattern`](crate::rle_decode_with_pattern), with the buffers spread across
+/// the `rayon` thread pool. The pattern is only read, so it is shared by all of them.
+///
+/// # Panics
+/// * `pattern` is empty
+/// * `fill_length + buffer.len()` would overflow for any of the buffers
+pub fn rle_fill_many<T>(
+    pattern: &[T],
+    fills: &mut [(&mut Vec<T>, usize)],
+) where T: Copy + Send + Sync {
+    // Checked up front, so an empty pattern panics on the calling thread
+    if let Err(err) = check_fragment(pattern.len()) {
+        decode_fail(err);
+    }
+    fills.par_iter_mut().for_each(|(buffer, fill_length)| {
+        rle_decode_with_pattern(pattern, *fill_length, buffer);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rle_decode;
+
+    #[test]
+    fn test_matches_serial() {
+        let prefix: Vec<u8> = (0..5000u32).map(|i| (i * 13 % 256) as u8).collect();
+        for &lookbehind in &[1, 2, 3, 333, 4096, 5000] {
+            for &fill in &[0, 1, CHUNK_BYTES - 1, CHUNK_BYTES + 1, 5 * CHUNK_BYTES + 77] {
+                let mut parallel = prefix.clone();
+                rle_decode_parallel_with_threshold(&mut parallel, lookbehind, fill, 0);
+                let mut serial = prefix.clone();
+                rle_decode(&mut serial, lookbehind, fill);
+                assert!(parallel == serial, "lookbehind {}, fill {}", lookbehind, fill);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wider_items() {
+        let mut parallel: Vec<[u8; 3]> = vec![[1, 2, 3], [4, 5, 6], [7, 8, 9]];
+        let mut serial = parallel.clone();
+        rle_decode_parallel_with_threshold(&mut parallel, 2, 3 * CHUNK_BYTES, 0);
+        rle_decode(&mut serial, 2, 3 * CHUNK_BYTES);
+        assert!(parallel == serial);
+    }
+
+    #[test]
+    fn test_fill_many_matches_serial() {
+        let pattern = [1u16, 2, 3, 4, 5];
+        let mut buffers: Vec<Vec<u16>> = (0..50).map(|i| vec![9; i % 4]).collect();
+        let expected: Vec<Vec<u16>> = buffers.iter().enumerate().map(|(i, buffer)| {
+            let mut buffer = buffer.clone();
+            rle_decode_with_pattern(&pattern, i * 37, &mut buffer);
+            buffer
+        }).collect();
+
+        let mut fills: Vec<(&mut Vec<u16>, usize)> =
+            buffers.iter_mut().enumerate().map(|(i, buffer)| (buffer, i * 37)).collect();
+        rle_fill_many(&pattern, &mut fills);
+        assert_eq!(buffers, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_fill_many_empty_pattern() {
+        rle_fill_many::<u8>(&[], &mut [(&mut Vec::new(), 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "attempt to repeat fragment of size 0")]
+    fn test_zero_lookbehind() {
+        rle_decode_parallel(&mut vec![1, 2, 3], 0, 10);
+    }
+}
@@ -0,0 +1,89 @@
+use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+use core::mem::MaybeUninit;
+use core::{cmp, ptr};
+
+/// Largest single copy, and how far ahead of it the destination is prefetched
+const CHUNK_SIZE: usize = 4096;
+
+/// Granularity of the prefetch hints
+const CACHE_LINE: usize = 64;
+
+/// Shorter fills stay in cache anyway, so the hints would only cost instructions
+const MIN_FILL: usize = 64 * 1024;
+
+/// Returns `true` if [`repeat_bytes_prefetch`] should be used for this fill
+#[inline(always)]
+pub(crate) fn is_supported(fill_bytes: usize) -> bool {
+    fill_bytes >= MIN_FILL
+}
+
+/// Writes `fill_bytes` bytes to `dst` by repeating the `lookbehind_bytes` bytes right before it,
+/// prefetching the destination one chunk ahead of the copies
+///
+/// # Safety
+/// * `lookbehind_bytes` is not 0
+/// * the `lookbehind_bytes` bytes before `dst` are readable
+/// * `fill_bytes` bytes starting at `dst` are writable
+#[inline(never)]
+pub(crate) unsafe fn repeat_bytes_prefetch(
+    dst: *mut MaybeUninit<u8>,
+    lookbehind_bytes: usize,
+    fill_bytes: usize,
+) {
+    // `distance` is always a whole number of fragments, and at most as far back as the
+    // pattern reaches, so copying from `distance` bytes back continues the pattern
+    let mut distance = lookbehind_bytes;
+    let mut written = 0;
+    while written < fill_bytes {
+        let copy_size = cmp::min(cmp::min(distance, CHUNK_SIZE), fill_bytes - written);
+        prefetch(dst, written + copy_size, fill_bytes);
+        ptr::copy_nonoverlapping(dst.add(written).sub(distance), dst.add(written), copy_size);
+        written += copy_size;
+        if distance < CHUNK_SIZE && 2 * distance <= lookbehind_bytes + written {
+            distance *= 2;
+        }
+    }
+}
+
+/// Hints the chunk of the destination starting at `offset` into the cache
+#[inline(always)]
+unsafe fn prefetch(dst: *mut MaybeUninit<u8>, offset: usize, fill_bytes: usize) {
+    let end = cmp::min(offset + CHUNK_SIZE, fill_bytes);
+    let mut line = offset;
+    while line < end {
+        // Prefetches never fault, but the address is still kept inside the buffer
+        _mm_prefetch::<_MM_HINT_T0>(dst.add(line) as *const i8);
+        line += CACHE_LINE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::repeat_by_doubling;
+
+    #[test]
+    fn test_matches_scalar() {
+        for &lookbehind in &[1, 2, 3, 63, 64, 333, 4095, 4096, 5000, 20_000] {
+            for &fill in &[MIN_FILL, MIN_FILL + 1, 3 * CHUNK_SIZE + 17 + MIN_FILL, 300_000] {
+                let mut prefetched: Vec<u8> =
+                    (0..lookbehind).map(|i| (i * 7 % 251) as u8).collect();
+                let mut scalar = prefetched.clone();
+                prefetched.reserve(fill);
+                scalar.reserve(fill);
+                unsafe {
+                    repeat_bytes_prefetch(
+                        prefetched.as_mut_ptr().add(lookbehind).cast(),
+                        lookbehind,
+                        fill,
+                    );
+                    prefetched.set_len(lookbehind + fill);
+                    repeat_by_doubling(&mut scalar, lookbehind, fill);
+                }
+                assert!(prefetched == scalar, "lookbehind {}, fill {}", lookbehind, fill);
+            }
+        }
+    }
+}
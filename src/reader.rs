@@ -0,0 +1,187 @@
+use core::cmp;
+use std::io::{self, Read};
+
+use crate::{RleCommand, RleError, RleRingWindow};
+
+/// Decodes a command stream on demand, as a [`Read`] source
+///
+/// The pull counterpart of [`RleWriter`](crate::RleWriter): every `read` decodes just enough
+/// of the commands to fill the caller's buffer, so the full output is never held in memory.
+/// Literal items are taken in order from `literals`, like in
+/// [`rle_decode_batch`](crate::rle_decode_batch). Only the last `window_size` bytes are
+/// retained, so that is the longest back-reference the commands may use.
+///
+/// Runs longer than the caller's buffer are split across several reads.
+///
+/// ```rust
+/// use std::io::Read;
+/// use rle_decode_helper::{RleCommand, RleReader};
+///
+/// let commands = [
+///     RleCommand::Literal(2),
+///     RleCommand::Copy { lookbehind_length: 2, fill_length: 5 },
+/// ];
+/// let mut reader = RleReader::new(commands, b"ab", 16);
+/// let mut out = Vec::new();
+/// reader.read_to_end(&mut out).unwrap();
+/// assert_eq!(out, b"abababa");
+/// ```
+#[derive(Debug)]
+pub struct RleReader<'a, C> {
+    commands: C,
+    literals: &'a [u8],
+    window: RleRingWindow<u8>,
+    /// The rest of the command being decoded, if any
+    pending: Option<RleCommand>,
+}
+
+impl<'a, C> RleReader<'a, C> where C: Iterator<Item = RleCommand> {
+    /// Creates a reader that decodes `commands`, allowing back-references up to `window_size`
+    /// bytes
+    ///
+    /// # Panics
+    /// Panics if `window_size` is 0.
+    pub fn new<S>(commands: S, literals: &'a [u8], window_size: usize) -> Self
+    where S: IntoIterator<IntoIter = C, Item = RleCommand> {
+        RleReader {
+            commands: commands.into_iter(),
+            literals,
+            window: RleRingWindow::new(window_size),
+            pending: None,
+        }
+    }
+
+    /// Decodes the next piece of the stream into the window
+    ///
+    /// Returns `false` once all commands were decoded.
+    fn produce(&mut self) -> Result<bool, RleError> {
+        let command = match self.pending.take().or_else(|| self.commands.next()) {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        let available = self.window.available();
+        match command {
+            RleCommand::Literal(n) => {
+                if n > self.literals.len() {
+                    return Err(RleError::TruncatedInput);
+                }
+                let count = cmp::min(n, available);
+                let (now, rest) = self.literals.split_at(count);
+                self.window.push_literals(now)?;
+                self.literals = rest;
+                if count < n {
+                    self.pending = Some(RleCommand::Literal(n - count));
+                }
+            }
+            RleCommand::Copy { lookbehind_length, fill_length } => {
+                // A tail repeat is periodic, so it can be resumed with the same distance
+                let count = cmp::min(fill_length, available);
+                self.window.copy_back(lookbehind_length, count)?;
+                if count < fill_length {
+                    self.pending = Some(RleCommand::Copy {
+                        lookbehind_length,
+                        fill_length: fill_length - count,
+                    });
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<'a, C> Read for RleReader<'a, C> where C: Iterator<Item = RleCommand> {
+    /// Decodes into `buf`
+    ///
+    /// # Errors
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] wrapping an [`RleError`] if the
+    /// commands are invalid, see [`RleRingWindow::copy_back`]. [`RleError::TruncatedInput`]
+    /// means they take more items than `literals` holds.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let (first, second) = self.window.output();
+            if !first.is_empty() {
+                let n = cmp::min(buf.len(), first.len());
+                buf[..n].copy_from_slice(&first[..n]);
+                // Wrapped output is only copied if the start fit completely
+                let m = if n == first.len() { cmp::min(buf.len() - n, second.len()) } else { 0 };
+                buf[n..(n + m)].copy_from_slice(&second[..m]);
+                self.window.consume(n + m);
+                return Ok(n + m);
+            }
+            let produced = self.produce()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if !produced {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::{rle_decode_batch, rle_encode};
+
+    #[test]
+    fn test_copy_matches_batch() {
+        let input: Vec<u8> = (0..20_000u32).map(|i| ((i / 17) % 11 + i / 3000) as u8).collect();
+        let mut literals = Vec::new();
+        let commands = rle_encode(&input, &mut literals);
+        let mut batch = Vec::new();
+        rle_decode_batch(&mut batch, &literals, &commands).unwrap();
+
+        // Windows smaller and larger than the longest run
+        for &window_size in &[1024, 1500, 1 << 16] {
+            let mut reader = RleReader::new(commands.iter().copied(), &literals, window_size);
+            let mut out = Vec::new();
+            io::copy(&mut reader, &mut out).unwrap();
+            assert!(out == batch, "window size {}", window_size);
+        }
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let commands = [
+            RleCommand::Literal(3),
+            RleCommand::Copy { lookbehind_length: 3, fill_length: 40 },
+            RleCommand::Literal(1),
+        ];
+        let mut expected = Vec::new();
+        rle_decode_batch(&mut expected, b"abcd", &commands).unwrap();
+
+        let mut reader = RleReader::new(commands, b"abcd", 7);
+        let mut out = Vec::new();
+        let mut chunk = [0; 2];
+        loop {
+            let n = reader.read(&mut chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&chunk[..n]);
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_invalid_commands() {
+        let mut reader = RleReader::new([RleCommand::Literal(3)], b"ab", 4);
+        let err = reader.read(&mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "encoded input ended unexpectedly");
+
+        let commands = [
+            RleCommand::Literal(2),
+            RleCommand::Copy { lookbehind_length: 5, fill_length: 1 },
+        ];
+        let mut reader = RleReader::new(commands, b"ab", 4);
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.to_string(), "attempt to repeat fragment larger than buffer size (5 > 2)");
+        assert_eq!(out, b"ab");
+    }
+}
@@ -0,0 +1,273 @@
+use alloc::vec::Vec;
+use core::{cmp, ptr};
+
+use crate::{wrap_index, RleError};
+
+/// Fixed size window of the most recent output, for back-references in bounded memory
+///
+/// Holds the last `window_size` items in a ring buffer. [`copy_back`] can reference any of them,
+/// including when the source wraps around the physical end of the ring. Produced items stay
+/// readable through [`output`] until they are [`consume`]d. Unread items are never overwritten,
+/// so at most `window_size` items can be pending at a time. Longer runs can be split into
+/// several calls with the same distance, because a repeat of the tail is periodic.
+///
+/// ```rust
+/// use rle_decode_helper::RleRingWindow;
+///
+/// let mut window = RleRingWindow::new(4);
+/// window.push_literals(b"abc").unwrap();
+/// assert_eq!(window.output(), (&b"abc"[..], &b""[..]));
+/// window.consume(3);
+///
+/// window.copy_back(3, 4).unwrap();
+/// let (first, second) = window.output();
+/// assert_eq!([first, second].concat(), b"abca");
+/// ```
+///
+/// [`copy_back`]: Self::copy_back
+/// [`output`]: Self::output
+/// [`consume`]: Self::consume
+#[derive(Debug, Clone)]
+pub struct RleRingWindow<T> {
+    ring: Vec<T>,
+    window_size: usize,
+    /// Physical index the next item is written to
+    head: usize,
+    /// Number of items before `head` that were not consumed yet
+    unread: usize,
+}
+
+impl<T> RleRingWindow<T> where T: Copy {
+    /// Creates a window that remembers the last `window_size` items
+    ///
+    /// # Panics
+    /// Panics if `window_size` is 0.
+    pub fn new(window_size: usize) -> Self {
+        assert!(window_size > 0, "window size must not be 0");
+        RleRingWindow {
+            ring: Vec::with_capacity(window_size),
+            window_size,
+            head: 0,
+            unread: 0,
+        }
+    }
+
+    /// Returns how many more items can be produced before the output has to be consumed
+    pub fn available(&self) -> usize {
+        self.window_size - self.unread
+    }
+
+    /// Appends literal items
+    ///
+    /// # Errors
+    /// Returns [`RleError::OutputTooLarge`] if there is not enough space for the literals
+    /// without overwriting unread output. Nothing is written in that case.
+    pub fn push_literals(&mut self, literals: &[T]) -> Result<(), RleError> {
+        self.check_available(literals.len())?;
+        let mut written = 0;
+        while written < literals.len() {
+            let count = cmp::min(literals.len() - written, self.window_size - self.head);
+            unsafe {
+                // `head + count <= window_size <= capacity`, and the literals are a separate
+                // allocation
+                ptr::copy_nonoverlapping(
+                    literals.as_ptr().add(written),
+                    self.ring.as_mut_ptr().add(self.head),
+                    count,
+                );
+                self.advance(count);
+            }
+            written += count;
+        }
+        self.unread += literals.len();
+        Ok(())
+    }
+
+    /// Appends `length` items by repeating the items `distance` positions back
+    ///
+    /// `distance` may be smaller than `length`, in which case the copy repeats the last
+    /// `distance` items like [`rle_decode`](crate::rle_decode) does.
+    ///
+    /// # Errors
+    /// * [`RleError::ZeroLookbehind`] if `distance` is 0
+    /// * [`RleError::EmptyBuffer`] if nothing was produced yet
+    /// * [`RleError::LookbehindTooLarge`] if `distance` is larger than the number of items in
+    ///   the window
+    /// * [`RleError::OutputTooLarge`] if there is not enough space for `length` items without
+    ///   overwriting unread output
+    ///
+    /// Nothing is written if an error is returned.
+    pub fn copy_back(&mut self, distance: usize, length: usize) -> Result<(), RleError> {
+        if distance == 0 {
+            return Err(RleError::ZeroLookbehind);
+        }
+        if self.ring.is_empty() {
+            return Err(RleError::EmptyBuffer);
+        }
+        if distance > self.ring.len() {
+            return Err(RleError::LookbehindTooLarge {
+                lookbehind: distance,
+                buffer_len: self.ring.len(),
+            });
+        }
+        self.check_available(length)?;
+
+        let mut written = 0;
+        while written < length {
+            // Any multiple of `distance` within the items written so far repeats the same
+            // pattern, so the copies can grow like in the decode loop of `rle_decode`
+            let reach = cmp::min(distance + written, self.ring.len());
+            let period = reach - reach % distance;
+            let src = wrap_index(self.head + self.window_size - period, self.window_size);
+            let count = cmp::min(
+                cmp::min(length - written, period),
+                cmp::min(self.window_size - src, self.window_size - self.head),
+            );
+            unsafe {
+                // Neither range crosses the end of the ring. They may overlap physically,
+                // since the destination overwrites the oldest items, but no item is written
+                // before it is read: `count <= period` keeps the destination of every item
+                // ahead of the sources still to come. `ptr::copy` is fine with the overlap.
+                // `src + count <= head` while the ring is filling up, so the source is
+                // initialized.
+                let base = self.ring.as_mut_ptr();
+                ptr::copy(base.add(src), base.add(self.head), count);
+                self.advance(count);
+            }
+            written += count;
+        }
+        self.unread += length;
+        Ok(())
+    }
+
+    /// Returns the items produced but not consumed yet, oldest first
+    ///
+    /// The items are split in two slices where they wrap around the end of the ring. The
+    /// second slice is empty if they do not.
+    pub fn output(&self) -> (&[T], &[T]) {
+        let start = wrap_index(self.head + self.window_size - self.unread, self.window_size);
+        if start + self.unread <= self.window_size {
+            (&self.ring[start..(start + self.unread)], &[])
+        } else {
+            let wrapped = start + self.unread - self.window_size;
+            (&self.ring[start..], &self.ring[..wrapped])
+        }
+    }
+
+    /// Marks the oldest `count` unread items as consumed
+    ///
+    /// They remain available for back-references.
+    ///
+    /// # Panics
+    /// Panics if `count` is larger than the number of unread items.
+    pub fn consume(&mut self, count: usize) {
+        assert!(count <= self.unread, "consumed more items than were produced");
+        self.unread -= count;
+    }
+
+    fn check_available(&self, count: usize) -> Result<(), RleError> {
+        if count > self.available() {
+            return Err(RleError::OutputTooLarge {
+                len: self.unread.saturating_add(count),
+                max_len: self.window_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Moves `head` past `count` freshly written items
+    ///
+    /// # Safety
+    /// `ring[head..(head + count)]` was initialized, and `head + count <= window_size`.
+    #[inline]
+    unsafe fn advance(&mut self, count: usize) {
+        let end = self.head + count;
+        if end > self.ring.len() {
+            self.ring.set_len(end);
+        }
+        self.head = wrap_index(end, self.window_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rle_decode, xorshift};
+
+    fn take_output(window: &mut RleRingWindow<u32>, out: &mut Vec<u32>) {
+        let (first, second) = window.output();
+        let count = first.len() + second.len();
+        out.extend_from_slice(first);
+        out.extend_from_slice(second);
+        window.consume(count);
+    }
+
+    #[test]
+    fn test_matches_rle_decode() {
+        let mut seed = 0x9E37_79B9u32;
+        let mut next = move || xorshift(&mut seed) as usize;
+        for &window_size in &[1, 2, 7, 16, 100] {
+            let mut window = RleRingWindow::new(window_size);
+            let mut out = Vec::new();
+            let mut expected = Vec::new();
+            for step in 0..500u32 {
+                if expected.is_empty() || next() % 3 == 0 {
+                    let count = 1 + next() % window_size;
+                    let literals: Vec<u32> = (0..count as u32).map(|i| step * 1000 + i).collect();
+                    window.push_literals(&literals).unwrap();
+                    expected.extend_from_slice(&literals);
+                } else {
+                    let distance = 1 + next() % cmp::min(window_size, expected.len());
+                    let length = next() % (window_size + 1);
+                    window.copy_back(distance, length).unwrap();
+                    rle_decode(&mut expected, distance, length);
+                }
+                take_output(&mut window, &mut out);
+            }
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn test_copy_wraps_around() {
+        let mut window = RleRingWindow::new(5);
+        let mut out = Vec::new();
+        window.push_literals(&[1, 2, 3, 4]).unwrap();
+        take_output(&mut window, &mut out);
+        window.push_literals(&[5, 6, 7]).unwrap();
+        // The ring now holds [6, 7, 3, 4, 5] and the source [4, 5, 6] crosses the end
+        assert_eq!(window.output(), (&[5][..], &[6, 7][..]));
+        take_output(&mut window, &mut out);
+        window.copy_back(4, 5).unwrap();
+        take_output(&mut window, &mut out);
+        assert_eq!(out, &[1, 2, 3, 4, 5, 6, 7, 4, 5, 6, 7, 4]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut window = RleRingWindow::new(4);
+        assert_eq!(window.copy_back(1, 1), Err(RleError::EmptyBuffer));
+        window.push_literals(&[1u8, 2, 3]).unwrap();
+        assert_eq!(window.copy_back(0, 1), Err(RleError::ZeroLookbehind));
+        assert_eq!(window.copy_back(1, 2), Err(RleError::OutputTooLarge { len: 5, max_len: 4 }));
+        assert_eq!(window.push_literals(&[4, 5]), Err(RleError::OutputTooLarge {
+            len: 5,
+            max_len: 4,
+        }));
+        assert_eq!(window.output(), (&[1, 2, 3][..], &[][..]));
+        window.consume(3);
+        assert_eq!(window.available(), 4);
+        assert_eq!(window.copy_back(4, 1), Err(RleError::LookbehindTooLarge {
+            lookbehind: 4,
+            buffer_len: 3,
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "consumed more items than were produced")]
+    fn test_consume_too_much() {
+        let mut window = RleRingWindow::new(4);
+        window.push_literals(&[1u8]).unwrap();
+        window.consume(2);
+    }
+}
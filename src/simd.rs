@@ -0,0 +1,145 @@
+use core::mem::MaybeUninit;
+use core::{cmp, ptr};
+
+/// Longest pattern that is repeated with wide stores
+const MAX_PATTERN_LENGTH: usize = 16;
+
+/// Size of every store
+const BLOCK_SIZE: usize = 32;
+
+/// Shorter fills are left to the doubling loop, which needs only a few copies for them
+const MIN_FILL: usize = 2 * BLOCK_SIZE;
+
+/// Returns `true` if [`repeat_pattern`] should be used for this fill
+#[inline(always)]
+pub(crate) fn is_supported(pattern_length: usize, count: usize) -> bool {
+    // A pattern of 1 byte is a plain memset, which `fill_single` already does well
+    (2..=MAX_PATTERN_LENGTH).contains(&pattern_length) && count >= MIN_FILL
+}
+
+/// Writes `count` bytes to `dst` by repeating the `pattern_length` bytes right before it
+///
+/// The bytes are only ever moved and never inspected, so they may be parts of any `Copy` type,
+/// or even be uninitialized.
+///
+/// # Safety
+/// * `2 <= pattern_length <= MAX_PATTERN_LENGTH`
+/// * the `pattern_length` bytes before `dst` are readable
+/// * `count` bytes starting at `dst` are writable, and do not overlap the pattern
+#[inline]
+pub(crate) unsafe fn repeat_pattern(dst: *mut MaybeUninit<u8>, pattern_length: usize, count: usize) {
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        if std::is_x86_feature_detected!("avx") {
+            return repeat_pattern_avx(dst, pattern_length, count);
+        }
+    }
+    repeat_pattern_portable(dst, pattern_length, count)
+}
+
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+#[target_feature(enable = "avx")]
+unsafe fn repeat_pattern_avx(dst: *mut MaybeUninit<u8>, pattern_length: usize, count: usize) {
+    repeat_pattern_portable(dst, pattern_length, count)
+}
+
+/// Compiles to 32 byte vector moves where the target has them, 16 byte ones otherwise
+#[inline(always)]
+unsafe fn repeat_pattern_portable(
+    dst: *mut MaybeUninit<u8>,
+    pattern_length: usize,
+    count: usize,
+) {
+    // Broadcast the pattern into a whole block, which may end in the middle of a repetition
+    let mut block = [MaybeUninit::<u8>::uninit(); BLOCK_SIZE];
+    let block_ptr = block.as_mut_ptr();
+    ptr::copy_nonoverlapping(dst.sub(pattern_length), block_ptr, pattern_length);
+    let mut filled = pattern_length;
+    while filled < BLOCK_SIZE {
+        let copy_size = cmp::min(filled, BLOCK_SIZE - filled);
+        ptr::copy_nonoverlapping(block_ptr, block_ptr.add(filled), copy_size);
+        filled += copy_size;
+    }
+
+    // Advancing by whole repetitions only, every store starts at the beginning of the pattern.
+    // Consecutive stores overlap by the partial repetition at the end of the block.
+    let stride = BLOCK_SIZE - BLOCK_SIZE % pattern_length;
+    let mut offset = 0;
+    while count - offset >= BLOCK_SIZE {
+        ptr::copy_nonoverlapping(block.as_ptr(), dst.add(offset), BLOCK_SIZE);
+        offset += stride;
+    }
+    ptr::copy_nonoverlapping(block.as_ptr(), dst.add(offset), count - offset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::{repeat_by_doubling, xorshift, RleBuffer};
+
+    #[test]
+    fn test_matches_scalar() {
+        let mut seed = 0x1234_5678;
+        for _ in 0..5000 {
+            let prefix_len = 20 + xorshift(&mut seed) as usize % 64;
+            let prefix: Vec<u8> = (0..prefix_len).map(|_| xorshift(&mut seed) as u8).collect();
+            let lookbehind = 1 + xorshift(&mut seed) as usize % 20;
+            let fill = match xorshift(&mut seed) % 3 {
+                0 => xorshift(&mut seed) as usize % (2 * MIN_FILL),
+                1 => xorshift(&mut seed) as usize % 1000,
+                _ => xorshift(&mut seed) as usize % 100_000,
+            };
+
+            let mut wide = prefix.clone();
+            wide.reserve(fill);
+            let mut scalar = prefix;
+            scalar.reserve(fill);
+            unsafe {
+                wide.repeat_tail_unchecked(lookbehind, fill);
+                repeat_by_doubling(&mut scalar, lookbehind, fill);
+            }
+            assert_eq!(wide, scalar, "lookbehind {}, fill {}", lookbehind, fill);
+        }
+    }
+
+    #[test]
+    fn test_portable_matches_scalar() {
+        for lookbehind in 2..=MAX_PATTERN_LENGTH {
+            for fill in (0..100).chain(Some(1000)) {
+                let mut wide: Vec<u8> = (0..lookbehind as u8).collect();
+                let mut scalar = wide.clone();
+                wide.reserve(fill);
+                scalar.reserve(fill);
+                unsafe {
+                    repeat_pattern_portable(
+                        wide.as_mut_ptr().add(lookbehind).cast(),
+                        lookbehind,
+                        fill,
+                    );
+                    wide.set_len(lookbehind + fill);
+                    repeat_by_doubling(&mut scalar, lookbehind, fill);
+                }
+                assert_eq!(wide, scalar);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wider_items() {
+        let mut wide: Vec<u16> = vec![1, 2, 3, 4, 5];
+        let mut scalar = wide.clone();
+        crate::rle_decode(&mut wide, 3, 200);
+        scalar.reserve(200);
+        unsafe { repeat_by_doubling(&mut scalar, 3, 200) };
+        assert_eq!(wide, scalar);
+    }
+
+    #[test]
+    fn test_single_byte_types() {
+        let mut bools = vec![true, false, false];
+        crate::rle_decode(&mut bools, 3, 40);
+        assert!(bools.chunks(3).all(|chunk| chunk == &[true, false, false][..chunk.len()]));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{decode_fail, fragment_start, RleBuffer};
+
+/// Counters accumulated by [`rle_decode_with_stats`] over a decode session
+///
+/// Start from `RleStats::default()` and pass the same value to every call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RleStats {
+    /// Number of block copies made, for all runs together
+    pub iterations: u64,
+    /// Number of items appended, for all runs together
+    pub elements: u64,
+    /// The longest single fill so far
+    pub max_fill: usize,
+}
+
+/// Decoding of run length encoded data that records what it did
+///
+/// Appends exactly like [`rle_decode`](crate::rle_decode), and adds the work done to `stats`.
+/// An iteration is one block copy of the doubling loop. Repeating a single item takes one,
+/// any other fill one for every doubling of the fragment and one for the rest. This is meant
+/// for tuning rather than production: the copies are always made by the portable loop,
+/// regardless of the `simd` feature.
+///
+/// ```rust
+/// use rle_decode_helper::{rle_decode_with_stats, RleStats};
+///
+/// let mut stats = RleStats::default();
+/// let mut buffer = vec![1, 2, 3];
+/// rle_decode_with_stats(&mut buffer, 3, 10, &mut stats);
+/// rle_decode_with_stats(&mut buffer, 1, 4, &mut stats);
+/// assert_eq!(stats, RleStats { iterations: 4, elements: 14, max_fill: 10 });
+/// ```
+///
+/// # Panics
+/// Under the same conditions as [`rle_decode`](crate::rle_decode).
+pub fn rle_decode_with_stats<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+    stats: &mut RleStats,
+) where T: Copy, B: RleBuffer<T> {
+    if let Err(err) = fragment_start(buffer.len(), lookbehind_length, fill_length) {
+        decode_fail(err)
+    }
+    buffer.reserve(fill_length);
+
+    let iterations = unsafe {
+        // The lengths were validated above, and space for all copies was reserved
+        repeat_counted(buffer, lookbehind_length, fill_length)
+    };
+    stats.iterations += iterations;
+    stats.elements += fill_length as u64;
+    stats.max_fill = stats.max_fill.max(fill_length);
+}
+
+/// The portable decode loop of `repeat_by_doubling()`, counting its copies
+///
+/// # Safety
+/// Same as [`RleBuffer::repeat_tail_unchecked`].
+unsafe fn repeat_counted<T, B>(
+    buffer: &mut B,
+    mut lookbehind_length: usize,
+    mut fill_length: usize,
+) -> u64 where B: RleBuffer<T> {
+    if fill_length == 0 {
+        return 0;
+    }
+    if lookbehind_length == 1 {
+        buffer.extend_from_last_unchecked(fill_length);
+        return 1;
+    }
+
+    let copy_fragment_start = buffer.len() - lookbehind_length;
+    let mut iterations = 1;
+    while fill_length > lookbehind_length {
+        buffer.extend_from_within_unchecked(copy_fragment_start, lookbehind_length);
+        fill_length -= lookbehind_length;
+        // Cannot overflow, that is the length of the buffer from `copy_fragment_start` on
+        lookbehind_length *= 2;
+        iterations += 1;
+    }
+    buffer.extend_from_within_unchecked(copy_fragment_start, fill_length);
+    iterations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    use crate::rle_decode;
+
+    #[test]
+    fn test_counters() {
+        let mut stats = RleStats::default();
+        let mut buffer: Vec<u16> = vec![1, 2, 3, 4, 5];
+        // Copies of 2, 4, 8 and the remaining 6 items
+        rle_decode_with_stats(&mut buffer, 2, 20, &mut stats);
+        assert_eq!(stats, RleStats { iterations: 4, elements: 20, max_fill: 20 });
+        rle_decode_with_stats(&mut buffer, 5, 0, &mut stats);
+        rle_decode_with_stats(&mut buffer, 25, 25, &mut stats);
+        rle_decode_with_stats(&mut buffer, 1, 7, &mut stats);
+        assert_eq!(stats, RleStats { iterations: 6, elements: 52, max_fill: 25 });
+
+        let mut expected: Vec<u16> = vec![1, 2, 3, 4, 5];
+        rle_decode(&mut expected, 2, 20);
+        rle_decode(&mut expected, 25, 25);
+        rle_decode(&mut expected, 1, 7);
+        assert_eq!(buffer, expected);
+    }
+}
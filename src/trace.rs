@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+use crate::RleBuffer;
+
+/// Fills of at least this many items get a span
+///
+/// Below it, the span would cost more than the fill it measures.
+pub(crate) const TRACE_MIN_FILL: usize = 1 << 16;
+
+/// The copy of [`rle_decode`](crate::rle_decode), inside a `rle_decode` span
+///
+/// The span carries `lookbehind_length` and `fill_length`, and `elapsed_ns` is recorded once
+/// the fill is done. Kept out of line, so small fills only pay for checking the threshold.
+///
+/// # Safety
+/// Same as [`rle_decode_unchecked`](crate::rle_decode_unchecked).
+#[cold]
+#[inline(never)]
+pub(crate) unsafe fn rle_decode_traced<T, B>(
+    buffer: &mut B,
+    lookbehind_length: usize,
+    fill_length: usize,
+) where T: Copy, B: RleBuffer<T> {
+    let span = tracing::debug_span!(
+        "rle_decode",
+        lookbehind_length,
+        fill_length,
+        elapsed_ns = tracing::field::Empty,
+    );
+    let _entered = span.enter();
+    let start = Instant::now();
+    crate::rle_decode_unchecked(buffer, lookbehind_length, fill_length);
+    span.record("elapsed_ns", start.elapsed().as_nanos() as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rle_decode;
+
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// The fields of every span created, in order
+    #[derive(Default)]
+    struct SpanLog {
+        spans: Mutex<Vec<Vec<(&'static str, u64)>>>,
+        next_id: AtomicU64,
+    }
+
+    struct LogSubscriber(Arc<SpanLog>);
+
+    struct FieldLog<'a>(&'a mut Vec<(&'static str, u64)>);
+
+    impl Visit for FieldLog<'_> {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.push((field.name(), value));
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+    }
+
+    impl Subscriber for LogSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Vec::new();
+            span.record(&mut FieldLog(&mut fields));
+            let mut spans = self.0.spans.lock().unwrap();
+            spans.push(fields);
+            Id::from_u64(self.0.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.0.spans.lock().unwrap();
+            values.record(&mut FieldLog(&mut spans[span.into_u64() as usize - 1]));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn test_span_for_large_fills_only() {
+        let log = Arc::new(SpanLog::default());
+        tracing::subscriber::with_default(LogSubscriber(log.clone()), || {
+            let mut buffer = vec![1u8, 2, 3];
+            rle_decode(&mut buffer, 2, 4);
+            rle_decode(&mut buffer, 1, TRACE_MIN_FILL - 1);
+            assert!(log.spans.lock().unwrap().is_empty());
+
+            rle_decode(&mut buffer, 3, TRACE_MIN_FILL);
+            assert_eq!(buffer.len(), 7 + 2 * TRACE_MIN_FILL - 1);
+        });
+
+        let spans = log.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0][..2], [("lookbehind_length", 3), ("fill_length", 1 << 16)]);
+        assert_eq!(spans[0][2].0, "elapsed_ns");
+        assert_eq!(spans[0].len(), 3);
+    }
+}
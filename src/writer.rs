@@ -0,0 +1,197 @@
+use alloc::vec::Vec;
+use core::cmp;
+use std::io::{self, Write};
+
+use crate::{rle_decode, RleError};
+
+/// Decoded bytes are produced and flushed in chunks of at least this size
+const MIN_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Streams decoded output to a [`Write`] sink
+///
+/// Literals and back-references are decoded on the fly and written to the inner writer as
+/// they are produced, so the full output is never held in memory. Only the last
+/// `max_lookbehind` bytes are retained for back-references, even after they were flushed.
+/// Memory use stays bounded by about twice `max_lookbehind` plus a small chunk buffer.
+///
+/// Writing to the `RleWriter` through its [`Write`] impl is the same as calling
+/// [`write_literals`].
+///
+/// ```rust
+/// use rle_decode_helper::RleWriter;
+///
+/// let mut writer = RleWriter::new(Vec::new(), 16);
+/// writer.write_literals(b"ab").unwrap();
+/// writer.write_copy(2, 5).unwrap();
+/// assert_eq!(writer.into_inner(), b"abababa");
+/// ```
+///
+/// [`write_literals`]: Self::write_literals
+#[derive(Debug)]
+pub struct RleWriter<W> {
+    inner: W,
+    window: Vec<u8>,
+    max_lookbehind: usize,
+}
+
+impl<W> RleWriter<W> where W: Write {
+    /// Creates a writer that allows back-references up to `max_lookbehind` bytes
+    pub fn new(inner: W, max_lookbehind: usize) -> Self {
+        RleWriter {
+            inner,
+            window: Vec::new(),
+            max_lookbehind,
+        }
+    }
+
+    /// Writes literal bytes to the inner writer
+    pub fn write_literals(&mut self, literals: &[u8]) -> io::Result<()> {
+        self.inner.write_all(literals)?;
+        if literals.len() >= self.max_lookbehind {
+            self.window.clear();
+            self.window.extend_from_slice(&literals[(literals.len() - self.max_lookbehind)..]);
+        } else {
+            self.window.extend_from_slice(literals);
+            self.discard_history();
+        }
+        Ok(())
+    }
+
+    /// Writes `fill_length` bytes by repeating the last `lookbehind_length` bytes written
+    ///
+    /// # Errors
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] wrapping an [`RleError`] if
+    /// `lookbehind_length` is 0, exceeds `max_lookbehind` or exceeds the number of bytes written
+    /// so far. Errors of the inner writer are passed on; some of the output may have been written
+    /// in that case.
+    pub fn write_copy(&mut self, lookbehind_length: usize, fill_length: usize) -> io::Result<()> {
+        if lookbehind_length == 0 {
+            return Err(invalid_input(RleError::ZeroLookbehind));
+        }
+        if self.window.is_empty() {
+            return Err(invalid_input(RleError::EmptyBuffer));
+        }
+        let reachable = cmp::min(self.window.len(), self.max_lookbehind);
+        if lookbehind_length > reachable {
+            return Err(invalid_input(RleError::LookbehindTooLarge {
+                lookbehind: lookbehind_length,
+                buffer_len: reachable,
+            }));
+        }
+
+        // A tail repeat is periodic, so it can be resumed from the new tail after every chunk
+        let chunk_size = cmp::max(self.max_lookbehind, MIN_CHUNK_SIZE);
+        let mut remaining = fill_length;
+        while remaining > 0 {
+            let fill_size = cmp::min(remaining, chunk_size);
+            let start = self.window.len();
+            rle_decode(&mut self.window, lookbehind_length, fill_size);
+            self.inner.write_all(&self.window[start..])?;
+            remaining -= fill_size;
+            self.discard_history();
+        }
+        Ok(())
+    }
+
+    /// Returns a reference to the inner writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Drops bytes that can no longer be referenced, once there are enough to make it worth it
+    fn discard_history(&mut self) {
+        let excess = self.window.len().saturating_sub(self.max_lookbehind);
+        if excess > 0 && excess >= self.max_lookbehind {
+            self.window.drain(..excess);
+        }
+    }
+}
+
+impl<W> Write for RleWriter<W> where W: Write {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_literals(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn invalid_input(err: RleError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer that only accepts a few bytes per call, to check the writes are complete
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = cmp::min(buf.len(), 3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_matches_rle_decode() {
+        let mut writer = RleWriter::new(Trickle(Vec::new()), 100);
+        let mut expected = Vec::new();
+        for i in 0..20u8 {
+            let literals = [i, i.wrapping_mul(7), 3];
+            writer.write_literals(&literals).unwrap();
+            expected.extend_from_slice(&literals);
+
+            let lookbehind = 1 + (i as usize * 13) % cmp::min(100, expected.len());
+            let fill = (i as usize * 1013) % 20_000;
+            writer.write_copy(lookbehind, fill).unwrap();
+            rle_decode(&mut expected, lookbehind, fill);
+
+            assert!(writer.window.len() <= 2 * MIN_CHUNK_SIZE + 100);
+        }
+        assert_eq!(writer.into_inner().0, expected);
+    }
+
+    #[test]
+    fn test_window_survives_long_literals() {
+        let mut writer = RleWriter::new(Vec::new(), 4);
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_copy(4, 6).unwrap();
+        writer.write_literals(b"x").unwrap();
+        writer.write_copy(3, 3).unwrap();
+        assert_eq!(writer.get_ref(), b"0123456789678967x67x");
+    }
+
+    #[test]
+    fn test_invalid_copy() {
+        let mut writer = RleWriter::new(Vec::new(), 4);
+        let err = writer.write_copy(1, 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "attempt to repeat fragment of empty buffer");
+
+        writer.write_literals(b"0123456789").unwrap();
+        assert!(writer.write_copy(0, 1).is_err());
+        let err = writer.write_copy(5, 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "attempt to repeat fragment larger than buffer size (5 > 4)");
+        assert_eq!(writer.get_ref(), b"0123456789");
+    }
+}
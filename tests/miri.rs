@@ -0,0 +1,193 @@
+//! Soundness tests for the unsafe copies, sized to run quickly under Miri
+//!
+//! Run with `cargo +nightly miri test --test miri`. The cases target the aliasing and
+//! provenance corners of the raw pointer code: copies whose source is next to or inside the
+//! region that was just written, copies of nothing, ranges that end at the last item, and
+//! items larger than a byte.
+
+use std::mem::MaybeUninit;
+
+use rle_decode_helper::{
+    append_from_within,
+    rle_decode,
+    rle_decode_array,
+    rle_decode_at,
+    rle_decode_clone,
+    rle_decode_in_place,
+    rle_decode_slice,
+    rle_decode_slice_to,
+    rle_decode_uninit,
+    rle_decode_unchecked,
+    try_rle_decode,
+    RleRingWindow,
+};
+
+/// Builds a vector with no spare capacity, so every write past its end needs a reallocation
+fn exact(items: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut vec = items.to_vec();
+    vec.shrink_to_fit();
+    vec
+}
+
+#[test]
+fn test_source_adjacent_to_destination() {
+    // With a lookbehind of 2 and a long fill, every copy reads the items written by the
+    // previous one, right up to the destination
+    for lookbehind in 1..=4 {
+        let mut buf = exact(&[(1, 2), (3, 4), (5, 6), (7, 8)]);
+        rle_decode(&mut buf, lookbehind, 37);
+        assert_eq!(buf.len(), 41);
+        for i in 4..buf.len() {
+            assert_eq!(buf[i], buf[i - lookbehind]);
+        }
+    }
+}
+
+#[test]
+fn test_zero_count_copies() {
+    let mut buf = exact(&[(1, 1)]);
+    rle_decode(&mut buf, 1, 0);
+    append_from_within(&mut buf, 1..);
+    append_from_within(&mut buf, 0..0);
+    assert_eq!(try_rle_decode(&mut buf, 1, 0), Ok(()));
+
+    let mut empty: Vec<(u32, u32)> = Vec::new();
+    append_from_within(&mut empty, ..);
+    assert!(empty.is_empty());
+
+    let mut fill: [MaybeUninit<(u32, u32)>; 0] = [];
+    assert_eq!(rle_decode_uninit(&[(2, 2)], &mut fill), 0);
+    assert_eq!(buf, &[(1, 1)]);
+}
+
+#[test]
+fn test_inclusive_range_to_last_item() {
+    let mut buf = exact(&[(1, 0), (2, 0), (3, 0)]);
+    append_from_within(&mut buf, ..=2);
+    assert_eq!(buf, &[(1, 0), (2, 0), (3, 0), (1, 0), (2, 0), (3, 0)]);
+    let last = buf.len() - 1;
+    append_from_within(&mut buf, last..=last);
+    append_from_within(&mut buf, 1..=last);
+    assert_eq!(buf.len(), 12);
+    assert_eq!(buf[6..], [(3, 0), (2, 0), (3, 0), (1, 0), (2, 0), (3, 0)]);
+}
+
+#[test]
+fn test_fragment_apart_from_fill() {
+    // The fragment is at the start, every copy after the first reads from the fill
+    let mut buf = exact(&[(1, 1), (2, 2), (3, 3), (4, 4)]);
+    rle_decode_at(&mut buf, 0, 2, 11);
+    assert_eq!(buf.len(), 15);
+    assert_eq!(buf[4..7], [(1, 1), (2, 2), (1, 1)]);
+    assert_eq!(buf[14], (1, 1));
+}
+
+#[test]
+fn test_lookbehind_is_whole_buffer() {
+    let mut buf = exact(&[(9, 9), (8, 8)]);
+    rle_decode(&mut buf, 2, 5);
+    assert_eq!(buf, &[(9, 9), (8, 8), (9, 9), (8, 8), (9, 9), (8, 8), (9, 9)]);
+}
+
+#[test]
+fn test_unchecked_with_exact_capacity() {
+    let mut buf = Vec::with_capacity(10);
+    buf.extend_from_slice(&[(1u32, 2u32), (3, 4), (5, 6)]);
+    let spare = buf.capacity() - buf.len();
+    unsafe { rle_decode_unchecked(&mut buf, 2, spare) };
+    assert_eq!(buf.len(), buf.capacity());
+    assert_eq!(buf[buf.len() - 1], if spare % 2 == 0 { (5, 6) } else { (3, 4) });
+}
+
+#[test]
+fn test_slice_fill_in_place() {
+    let mut buf = [(0u32, 0u32); 12];
+    buf[2] = (1, 1);
+    buf[3] = (2, 2);
+    buf[4] = (3, 3);
+    assert_eq!(rle_decode_slice(&mut buf, 2, 3, 7), 7);
+    assert_eq!(buf[5..], [(1, 1), (2, 2), (3, 3), (1, 1), (2, 2), (3, 3), (1, 1)]);
+}
+
+#[test]
+fn test_slice_fill_before_pattern() {
+    // The fill ends right where the pattern starts, and is longer than it
+    let mut buf = [(0u32, 0u32); 9];
+    buf[7] = (1, 1);
+    buf[8] = (2, 2);
+    assert_eq!(rle_decode_slice_to(&mut buf, 7..9, 0, 7), Ok(7));
+    assert_eq!(buf[..7], [(1, 1), (2, 2), (1, 1), (2, 2), (1, 1), (2, 2), (1, 1)]);
+}
+
+#[test]
+fn test_in_place_fills_exactly_to_end() {
+    // Only the initialized prefix is read, and the fill ends at the last item of the region
+    let mut region = [MaybeUninit::<(u32, u32)>::uninit(); 9];
+    region[0] = MaybeUninit::new((1, 1));
+    region[1] = MaybeUninit::new((2, 2));
+    assert_eq!(unsafe { rle_decode_in_place(&mut region, 2, 2, 7) }, Ok(9));
+    let last = unsafe { region[8].assume_init() };
+    assert_eq!(last, (1, 1));
+}
+
+#[test]
+fn test_array_is_fully_initialized() {
+    let array: [(u32, u32); 7] = rle_decode_array(&[(1, 1), (2, 2)], 5).unwrap();
+    assert_eq!(array, [(1, 1), (2, 2), (1, 1), (2, 2), (1, 1), (2, 2), (1, 1)]);
+    let single: [(u32, u32); 2] = rle_decode_array(&[(3, 3), (4, 4)], 0).unwrap();
+    assert_eq!(single, [(3, 3), (4, 4)]);
+}
+
+#[test]
+fn test_padded_items() {
+    // The padding bytes are uninitialized, and copied along with the fields
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Padded(u8, u64);
+
+    let mut buf = vec![Padded(1, 10), Padded(2, 20), Padded(3, 30)];
+    rle_decode(&mut buf, 2, 9);
+    assert_eq!(buf[3..5], [Padded(2, 20), Padded(3, 30)]);
+    assert_eq!(buf[11], Padded(2, 20));
+}
+
+#[test]
+fn test_zero_sized_items() {
+    let mut buf = vec![(); 3];
+    rle_decode(&mut buf, 2, 100);
+    append_from_within(&mut buf, ..=2);
+    assert_eq!(buf.len(), 106);
+
+    let huge = usize::MAX / 4;
+    rle_decode(&mut buf, 1, huge);
+    rle_decode(&mut buf, 5, huge);
+    assert_eq!(buf.len(), 106 + 2 * huge);
+}
+
+#[test]
+fn test_clone_with_heap_items() {
+    let mut buf = vec![String::from("a"), String::from("b")];
+    rle_decode_clone(&mut buf, 2, 5);
+    assert_eq!(buf, ["a", "b", "a", "b", "a", "b", "a"]);
+}
+
+#[test]
+fn test_ring_window_wraparound() {
+    let mut window = RleRingWindow::new(3);
+    let mut out = Vec::new();
+    for i in 0..10u32 {
+        window.push_literals(&[(i, i)]).unwrap();
+        window.copy_back(1 + i as usize % 2, 2).unwrap();
+        let (first, second) = window.output();
+        out.extend_from_slice(first);
+        out.extend_from_slice(second);
+        let count = first.len() + second.len();
+        window.consume(count);
+    }
+
+    let mut expected = Vec::new();
+    for i in 0..10u32 {
+        expected.push((i, i));
+        rle_decode(&mut expected, 1 + i as usize % 2, 2);
+    }
+    assert_eq!(out, expected);
+}
@@ -0,0 +1,39 @@
+//! The fallible API without `std`
+//!
+//! Run with `cargo test --no-default-features --test no_std`. This crate is `#![no_std]` itself,
+//! so anything that only works with `std` in scope fails to compile here.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use rle_decode_helper::{try_rle_decode, RleError};
+
+#[test]
+fn test_try_decode() {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&[1u8, 2, 3]);
+    assert_eq!(try_rle_decode(&mut buf, 2, 3), Ok(()));
+    assert_eq!(buf, &[1, 2, 3, 2, 3, 2]);
+}
+
+#[test]
+fn test_errors() {
+    let mut buf = Vec::new();
+    buf.push(1u8);
+    assert_eq!(try_rle_decode(&mut buf, 0, 1), Err(RleError::ZeroLookbehind));
+    let err = try_rle_decode(&mut buf, 1, usize::MAX).unwrap_err();
+    assert_eq!(err, RleError::CapacityOverflow);
+    match try_rle_decode(&mut buf, 2, 1) {
+        Err(RleError::LookbehindTooLarge { lookbehind: 2, buffer_len: 1 }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(buf, &[1]);
+
+    let mut message = String::new();
+    write!(message, "{}", RleError::ZeroLookbehind).unwrap();
+    assert_eq!(message, "attempt to repeat fragment of size 0");
+}
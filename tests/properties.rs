@@ -0,0 +1,63 @@
+//! Randomized comparison of the decode functions against a naive repeat of the fragment
+//!
+//! Run with `cargo test --test properties`. The lengths are kept small enough for the default
+//! number of cases to finish in well under a second.
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use rle_decode_helper::{
+    rle_decode,
+    rle_decode_iter,
+    rle_decode_with_pattern,
+    try_rle_decode,
+};
+
+/// A buffer, a lookbehind length that fits into it, and a fill length
+fn decode_args() -> impl Strategy<Value = (Vec<u8>, usize, usize)> {
+    vec(any::<u8>(), 1..64)
+        .prop_flat_map(|buffer| {
+            let len = buffer.len();
+            (Just(buffer), 1..=len, 0..2000usize)
+        })
+}
+
+/// The buffer with `fill_length` items appended by cycling through its last `lookbehind`
+fn naive(buffer: &[u8], lookbehind: usize, fill_length: usize) -> Vec<u8> {
+    let pattern = &buffer[(buffer.len() - lookbehind)..];
+    let mut expected = buffer.to_vec();
+    expected.extend(pattern.iter().cycle().take(fill_length));
+    expected
+}
+
+proptest! {
+    #[test]
+    fn rle_decode_matches_naive((buffer, lookbehind, fill) in decode_args()) {
+        let mut decoded = buffer.clone();
+        rle_decode(&mut decoded, lookbehind, fill);
+        prop_assert_eq!(decoded, naive(&buffer, lookbehind, fill));
+    }
+
+    #[test]
+    fn try_rle_decode_matches_naive((buffer, lookbehind, fill) in decode_args()) {
+        let mut decoded = buffer.clone();
+        prop_assert_eq!(try_rle_decode(&mut decoded, lookbehind, fill), Ok(()));
+        prop_assert_eq!(decoded, naive(&buffer, lookbehind, fill));
+    }
+
+    #[test]
+    fn with_pattern_matches_naive((buffer, lookbehind, fill) in decode_args()) {
+        let pattern = &buffer[(buffer.len() - lookbehind)..];
+        let mut out = buffer.clone();
+        rle_decode_with_pattern(pattern, fill, &mut out);
+        prop_assert_eq!(out, naive(&buffer, lookbehind, fill));
+    }
+
+    #[test]
+    fn iter_matches_naive((buffer, lookbehind, fill) in decode_args()) {
+        let pattern = &buffer[(buffer.len() - lookbehind)..];
+        let mut out = buffer.clone();
+        out.extend(rle_decode_iter(pattern, fill));
+        prop_assert_eq!(out, naive(&buffer, lookbehind, fill));
+    }
+}